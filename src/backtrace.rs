@@ -0,0 +1,67 @@
+#[cfg(feature = "backtrace")]
+use std::{
+    backtrace::Backtrace,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use crate::observer::LockEvent;
+#[cfg(feature = "backtrace")]
+use crate::{observer, stats::micros_usize};
+
+// `usize::MAX` means capturing is off.
+#[cfg(feature = "backtrace")]
+static THRESHOLD_US: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Capture a backtrace on every acquisition, and report it to the observers
+/// once a wait or hold lasts longer than `threshold`. `None` turns capturing off again.
+#[cfg(feature = "backtrace")]
+pub fn set_backtrace_threshold(threshold: Option<Duration>) {
+    THRESHOLD_US.store(
+        threshold.map_or(usize::MAX, micros_usize),
+        Ordering::Release,
+    );
+}
+
+// The call path of one wait or hold. Capturing is cheap next to resolving
+// symbols, which only happens if the backtrace is actually printed.
+#[derive(Debug, Default)]
+pub(crate) struct Trace {
+    #[cfg(feature = "backtrace")]
+    backtrace: Option<Box<Backtrace>>,
+}
+
+impl Trace {
+    #[cfg(feature = "backtrace")]
+    pub(crate) fn capture() -> Self {
+        let enabled = THRESHOLD_US.load(Ordering::Acquire) != usize::MAX;
+        Self {
+            backtrace: if enabled {
+                Some(Box::new(Backtrace::force_capture()))
+            } else {
+                None
+            },
+        }
+    }
+
+    #[cfg(not(feature = "backtrace"))]
+    pub(crate) fn capture() -> Self {
+        Self {}
+    }
+
+    // Reports the backtrace the first time the wait or hold in `event` is
+    // past the threshold.
+    #[cfg(feature = "backtrace")]
+    pub(crate) fn report(&mut self, event: &LockEvent<'_>) {
+        let elapsed = event.held.or(event.waited).unwrap_or_default();
+        if micros_usize(elapsed) < THRESHOLD_US.load(Ordering::Acquire) {
+            return;
+        }
+        if let Some(backtrace) = self.backtrace.take() {
+            observer::notify(|o| o.on_backtrace(event, &backtrace));
+        }
+    }
+
+    #[cfg(not(feature = "backtrace"))]
+    pub(crate) fn report(&mut self, _event: &LockEvent<'_>) {}
+}
@@ -0,0 +1,396 @@
+//! Summarizes a binary log written by `trace_mutex::BinaryLogObserver`.
+//!
+//! Usage: trace-mutex-analyze <log> [top N]
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env, process,
+};
+
+use trace_mutex::binlog::{Event, EventKind, Reader, Record};
+
+#[derive(Default)]
+struct LockSummary {
+    acquisitions: u64,
+    contentions: u64,
+    timeouts: u64,
+    total_wait_us: u64,
+    holds_us: Histogram,
+}
+
+// Counts of values in buckets at most 1/16th wide, so percentiles are close
+// without keeping every value. Only buckets in use take memory.
+#[derive(Default)]
+struct Histogram {
+    buckets: BTreeMap<u32, u64>,
+    count: u64,
+    sum: u64,
+    max: u64,
+}
+
+impl Histogram {
+    fn add(&mut self, value: u64) {
+        *self.buckets.entry(bucket(value)).or_default() += 1;
+        self.count += 1;
+        self.sum = self.sum.saturating_add(value);
+        self.max = self.max.max(value);
+    }
+
+    // The upper bound of the bucket holding the `p`th percentile.
+    fn percentile(&self, p: u64) -> u64 {
+        let rank = (self.count - 1) * p / 100;
+        let mut seen = 0;
+        for (&bucket, &n) in &self.buckets {
+            seen += n;
+            if seen > rank {
+                return bucket_max(bucket).min(self.max);
+            }
+        }
+        self.max
+    }
+}
+
+// Values below 16 have a bucket each; every power of two above is split
+// into 16 buckets.
+fn bucket(value: u64) -> u32 {
+    if value < 16 {
+        return value as u32;
+    }
+    let exp = 63 - value.leading_zeros();
+    let sub = (value >> (exp - 4)) & 15;
+    (exp - 3) * 16 + sub as u32
+}
+
+fn bucket_max(bucket: u32) -> u64 {
+    if bucket < 16 {
+        return u64::from(bucket);
+    }
+    let (exp, sub) = (bucket / 16 + 3, u128::from(bucket % 16));
+    let max = ((16 + sub + 1) << (exp - 4)) - 1;
+    max.min(u128::from(u64::MAX)) as u64
+}
+
+#[derive(Default)]
+struct Analysis {
+    sites: HashMap<u32, String>,
+    names: HashMap<u64, String>,
+    threads: HashMap<u32, String>,
+    // The site each lock was created at, which is its lock class.
+    created: HashMap<u64, u32>,
+    events: u64,
+    missing: u64,
+    last_seq: Option<u64>,
+    locks: BTreeMap<u64, LockSummary>,
+    // (total wait, acquisitions) per call site. Keyed by the site's text,
+    // since sites differing only in column share it.
+    site_waits: HashMap<String, (u64, u64)>,
+    // (total wait, acquisitions) per thread.
+    thread_waits: HashMap<u32, (u64, u64)>,
+    // Locks each thread holds, with the site they were taken at.
+    held: HashMap<u32, Vec<(u64, u32)>>,
+    // Lock orders seen: `from` was held at the first site when `to` was
+    // taken at the second, by the thread.
+    order: BTreeMap<u64, BTreeMap<u64, (u32, u32, u32)>>,
+}
+
+impl Analysis {
+    fn add(&mut self, record: Record) {
+        match record {
+            Record::Site { index, site } => {
+                self.sites.insert(index, site);
+            }
+            Record::Lock { id, name, created } => {
+                self.names.insert(id, name);
+                self.created.insert(id, created);
+            }
+            Record::Thread { index, name } => {
+                self.threads.insert(index, name);
+            }
+            Record::Event(event) => self.event(event),
+        }
+    }
+
+    fn event(&mut self, event: Event) {
+        self.events += 1;
+        if let Some(last) = self.last_seq {
+            self.missing += event.seq.saturating_sub(last + 1);
+        }
+        self.last_seq = Some(event.seq);
+
+        let lock = self.locks.entry(event.lock).or_default();
+        match event.kind {
+            EventKind::WaitStart => lock.contentions += 1,
+            EventKind::Acquired => {
+                lock.acquisitions += 1;
+                lock.total_wait_us += event.duration_us;
+                let site = self
+                    .site_waits
+                    .entry(self.site(event.site).to_owned())
+                    .or_default();
+                site.0 += event.duration_us;
+                site.1 += 1;
+                let thread = self.thread_waits.entry(event.thread).or_default();
+                thread.0 += event.duration_us;
+                thread.1 += 1;
+
+                let held = self.held.entry(event.thread).or_default();
+                for &(from, from_site) in held.iter().filter(|&&(from, _)| from != event.lock) {
+                    self.order
+                        .entry(from)
+                        .or_default()
+                        .entry(event.lock)
+                        .or_insert((from_site, event.site, event.thread));
+                }
+                held.push((event.lock, event.site));
+            }
+            EventKind::TimedOut => {
+                lock.timeouts += 1;
+                lock.total_wait_us += event.duration_us;
+                let site = self
+                    .site_waits
+                    .entry(self.site(event.site).to_owned())
+                    .or_default();
+                site.0 += event.duration_us;
+                self.thread_waits.entry(event.thread).or_default().0 += event.duration_us;
+            }
+            EventKind::Released => {
+                lock.holds_us.add(event.duration_us);
+                let held = self.held.entry(event.thread).or_default();
+                if let Some(pos) = held.iter().rposition(|&(id, _)| id == event.lock) {
+                    held.remove(pos);
+                }
+            }
+            EventKind::Poisoned => {}
+        }
+    }
+
+    fn lock_name(&self, id: u64) -> String {
+        let mut out = match self.names.get(&id) {
+            Some(name) if !name.is_empty() => format!("lock {} '{}'", id, name),
+            _ => format!("lock {}", id),
+        };
+        let created = self.class(id);
+        if created != "<unknown>" {
+            out.push_str(&format!(" (created at {})", created));
+        }
+        out
+    }
+
+    fn class(&self, id: u64) -> &str {
+        self.created
+            .get(&id)
+            .map_or("<unknown>", |&site| self.site(site))
+    }
+
+    fn site(&self, index: u32) -> &str {
+        self.sites.get(&index).map_or("<unknown>", String::as_str)
+    }
+
+    fn thread(&self, index: u32) -> String {
+        match self.threads.get(&index) {
+            Some(name) => format!("thread {}", name),
+            None => format!("thread #{}", index),
+        }
+    }
+
+    fn report(&self, top: usize) {
+        println!(
+            "{} events, {} locks, {} sites",
+            self.events,
+            self.locks.len(),
+            self.sites.len()
+        );
+        if self.missing > 0 {
+            println!("warning: {} events missing from the sequence", self.missing);
+        }
+
+        println!("\nMost contended locks:");
+        let mut locks: Vec<_> = self.locks.iter().collect();
+        locks.sort_by_key(|(_, lock)| {
+            (
+                std::cmp::Reverse(lock.contentions),
+                std::cmp::Reverse(lock.total_wait_us),
+            )
+        });
+        for (id, lock) in locks
+            .iter()
+            .take(top)
+            .filter(|(_, lock)| lock.contentions > 0)
+        {
+            println!(
+                "  {}: {} of {} acquisitions contended, {} timeouts, {} µs waited",
+                self.lock_name(**id),
+                lock.contentions,
+                lock.acquisitions,
+                lock.timeouts,
+                lock.total_wait_us
+            );
+        }
+
+        println!("\nMost contended lock classes:");
+        // (locks, contentions, acquisitions, total wait) per creation site.
+        let mut classes: HashMap<&str, (u64, u64, u64, u64)> = HashMap::new();
+        for (&id, lock) in &self.locks {
+            let class = classes.entry(self.class(id)).or_default();
+            class.0 += 1;
+            class.1 += lock.contentions;
+            class.2 += lock.acquisitions;
+            class.3 += lock.total_wait_us;
+        }
+        let mut classes: Vec<_> = classes.into_iter().collect();
+        classes.sort_by_key(|&(_, (_, contentions, _, wait))| {
+            (std::cmp::Reverse(contentions), std::cmp::Reverse(wait))
+        });
+        for (class, (locks, contentions, acquisitions, wait)) in classes
+            .iter()
+            .take(top)
+            .filter(|(_, (_, contentions, _, _))| *contentions > 0)
+        {
+            println!(
+                "  created at {}: {} locks, {} of {} acquisitions contended, {} µs waited",
+                class, locks, contentions, acquisitions, wait
+            );
+        }
+
+        println!("\nWorst call sites by total wait:");
+        let mut sites: Vec<_> = self.site_waits.iter().collect();
+        sites.sort_by_key(|(_, &(wait, _))| std::cmp::Reverse(wait));
+        for (site, (wait, acquisitions)) in
+            sites.iter().take(top).filter(|(_, &(wait, _))| wait > 0)
+        {
+            println!("  {}: {} µs over {} acquisitions", site, wait, acquisitions);
+        }
+
+        println!("\nThreads by total wait:");
+        let mut threads: Vec<_> = self.thread_waits.iter().collect();
+        threads.sort_by_key(|(_, &(wait, _))| std::cmp::Reverse(wait));
+        for (&thread, (wait, acquisitions)) in
+            threads.iter().take(top).filter(|(_, &(wait, _))| wait > 0)
+        {
+            println!(
+                "  {}: {} µs over {} acquisitions",
+                self.thread(thread),
+                wait,
+                acquisitions
+            );
+        }
+
+        println!("\nHold times (µs), by total hold:");
+        let mut holds: Vec<(u64, &Histogram)> = self
+            .locks
+            .iter()
+            .filter(|(_, lock)| lock.holds_us.count > 0)
+            .map(|(&id, lock)| (id, &lock.holds_us))
+            .collect();
+        holds.sort_by_key(|(_, holds)| std::cmp::Reverse(holds.sum));
+        for (id, holds) in holds.iter().take(top) {
+            println!(
+                "  {}: p50 {} p90 {} p99 {} max {} ({} holds)",
+                self.lock_name(*id),
+                holds.percentile(50),
+                holds.percentile(90),
+                holds.percentile(99),
+                holds.max,
+                holds.count
+            );
+        }
+
+        println!("\nLock-order inversions:");
+        let mut reported = BTreeSet::new();
+        for (&from, out) in &self.order {
+            for (&to, &(from_site, to_site, thread)) in out {
+                let path = match self.path(to, from) {
+                    Some(path) => path,
+                    None => continue,
+                };
+                let mut locks: Vec<u64> = path.clone();
+                locks.sort_unstable();
+                if !reported.insert(locks) {
+                    continue;
+                }
+                println!(
+                    "  {} (at {}) then {} (at {}) on {}, but also:",
+                    self.lock_name(from),
+                    self.site(from_site),
+                    self.lock_name(to),
+                    self.site(to_site),
+                    self.thread(thread)
+                );
+                for pair in path.windows(2) {
+                    let (a_site, b_site, thread) = self.order[&pair[0]][&pair[1]];
+                    println!(
+                        "    {} (at {}) then {} (at {}) on {}",
+                        self.lock_name(pair[0]),
+                        self.site(a_site),
+                        self.lock_name(pair[1]),
+                        self.site(b_site),
+                        self.thread(thread)
+                    );
+                }
+            }
+        }
+        if reported.is_empty() {
+            println!("  none");
+        }
+    }
+
+    // Depth-first search for a chain of lock orders `from` -> `to`.
+    fn path(&self, from: u64, to: u64) -> Option<Vec<u64>> {
+        let mut seen = BTreeSet::new();
+        let mut stack = vec![vec![from]];
+        while let Some(path) = stack.pop() {
+            let last = *path.last().expect("paths are never empty");
+            if last == to {
+                return Some(path);
+            }
+            if !seen.insert(last) {
+                continue;
+            }
+            for &next in self.order.get(&last).into_iter().flat_map(BTreeMap::keys) {
+                let mut longer = path.clone();
+                longer.push(next);
+                stack.push(longer);
+            }
+        }
+        None
+    }
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = match args.next() {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: trace-mutex-analyze <log> [top N]");
+            process::exit(2);
+        }
+    };
+    let top = match args.next().map(|n| n.parse()) {
+        None => 10,
+        Some(Ok(n)) => n,
+        Some(Err(err)) => {
+            eprintln!("invalid count: {}", err);
+            process::exit(2);
+        }
+    };
+
+    let reader = match Reader::open(&path) {
+        Ok(reader) => reader,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            process::exit(1);
+        }
+    };
+    let mut analysis = Analysis::default();
+    for record in reader {
+        match record {
+            Ok(record) => analysis.add(record),
+            Err(err) => {
+                // A log cut off mid-record still has everything before it.
+                eprintln!("{}: stopped reading: {}", path, err);
+                break;
+            }
+        }
+    }
+    analysis.report(top);
+}
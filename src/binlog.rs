@@ -0,0 +1,331 @@
+//! A compact binary log of lock events, for post-mortem analysis with the
+//! `trace-mutex-analyze` binary. Only built with the `binlog` feature.
+//!
+//! The log starts with `MAGIC`, followed by records. Each record is a tag
+//! byte and little-endian fields. Sites, locks and threads are defined once
+//! and then referred to by index.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::{Mutex as StdMutex, MutexGuard as StdMutexGuard, PoisonError as StdPoisonError},
+    thread::ThreadId,
+    time::Instant,
+};
+
+use crate::{
+    observer::{LockEvent, LockObserver},
+    stats::micros,
+    CallSite,
+};
+
+pub const MAGIC: &[u8; 8] = b"TMXLOG2\n";
+
+const TAG_SITE: u8 = 1;
+const TAG_LOCK: u8 = 2;
+const TAG_THREAD: u8 = 3;
+
+/// What happened to a lock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    WaitStart = 10,
+    Acquired = 11,
+    Released = 12,
+    TimedOut = 13,
+    Poisoned = 14,
+}
+
+impl EventKind {
+    fn from_tag(tag: u8) -> Option<Self> {
+        Some(match tag {
+            10 => EventKind::WaitStart,
+            11 => EventKind::Acquired,
+            12 => EventKind::Released,
+            13 => EventKind::TimedOut,
+            14 => EventKind::Poisoned,
+            _ => return None,
+        })
+    }
+}
+
+/// One lock event. `duration_us` is the wait for `Acquired` and `TimedOut`,
+/// the hold for `Released` and `Poisoned`, and zero otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub seq: u64,
+    pub kind: EventKind,
+    pub ts_us: u64,
+    pub thread: u32,
+    pub lock: u64,
+    pub site: u32,
+    pub duration_us: u64,
+}
+
+/// A record read back from a log.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Record {
+    Site {
+        index: u32,
+        site: String,
+    },
+    /// `created` is the index of the site the lock was created at.
+    Lock {
+        id: u64,
+        name: String,
+        created: u32,
+    },
+    Thread {
+        index: u32,
+        name: String,
+    },
+    Event(Event),
+}
+
+/// Writes every lock event to a binary log. Install it with `add_observer`.
+///
+/// Writes are buffered; call `flush` before reading the log back.
+pub struct BinaryLogObserver {
+    start: Instant,
+    out: StdMutex<Log>,
+}
+
+struct Log {
+    writer: Box<dyn Write + Send>,
+    seq: u64,
+    sites: HashMap<CallSite, u32>,
+    locks: HashSet<usize>,
+    threads: HashMap<ThreadId, u32>,
+}
+
+impl BinaryLogObserver {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn new(mut writer: impl Write + Send + 'static) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        Ok(Self {
+            start: Instant::now(),
+            out: StdMutex::new(Log {
+                writer: Box::new(writer),
+                seq: 0,
+                sites: HashMap::new(),
+                locks: HashSet::new(),
+                threads: HashMap::new(),
+            }),
+        })
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.out().writer.flush()
+    }
+
+    fn record(&self, kind: EventKind, event: &LockEvent<'_>, duration_us: u64) {
+        let ts_us = micros(self.start.elapsed());
+        // A failed write only loses log data; the lock itself is fine.
+        let _ = self.out().write(kind, event, ts_us, duration_us);
+    }
+
+    fn out(&self) -> StdMutexGuard<'_, Log> {
+        self.out.lock().unwrap_or_else(StdPoisonError::into_inner)
+    }
+}
+
+impl Log {
+    fn write(
+        &mut self,
+        kind: EventKind,
+        event: &LockEvent<'_>,
+        ts_us: u64,
+        duration_us: u64,
+    ) -> io::Result<()> {
+        // Definitions are only remembered once written, so after a failed
+        // write they are written again rather than referred to undefined.
+        let site = self.site(event.site)?;
+        if !self.locks.contains(&event.id) {
+            let created = self.site(event.created)?;
+            self.writer.write_all(&[TAG_LOCK])?;
+            self.writer.write_all(&(event.id as u64).to_le_bytes())?;
+            write_str(&mut self.writer, event.name.unwrap_or_default())?;
+            self.writer.write_all(&created.to_le_bytes())?;
+            self.locks.insert(event.id);
+        }
+        let thread = match self.threads.get(&event.thread.id()) {
+            Some(&index) => index,
+            None => {
+                let index = self.threads.len() as u32;
+                self.writer.write_all(&[TAG_THREAD])?;
+                self.writer.write_all(&index.to_le_bytes())?;
+                write_str(&mut self.writer, &event.thread.to_string())?;
+                self.threads.insert(event.thread.id(), index);
+                index
+            }
+        };
+
+        let mut buf = [0; 41];
+        buf[0] = kind as u8;
+        buf[1..9].copy_from_slice(&self.seq.to_le_bytes());
+        buf[9..17].copy_from_slice(&ts_us.to_le_bytes());
+        buf[17..21].copy_from_slice(&thread.to_le_bytes());
+        buf[21..29].copy_from_slice(&(event.id as u64).to_le_bytes());
+        buf[29..33].copy_from_slice(&site.to_le_bytes());
+        buf[33..41].copy_from_slice(&duration_us.to_le_bytes());
+        self.seq += 1;
+        self.writer.write_all(&buf)
+    }
+
+    // The index of `site`, defining it first if it is new.
+    fn site(&mut self, site: CallSite) -> io::Result<u32> {
+        if let Some(&index) = self.sites.get(&site) {
+            return Ok(index);
+        }
+        let index = self.sites.len() as u32;
+        self.writer.write_all(&[TAG_SITE])?;
+        self.writer.write_all(&index.to_le_bytes())?;
+        write_str(&mut self.writer, &site.to_string())?;
+        self.sites.insert(site, index);
+        Ok(index)
+    }
+}
+
+impl LockObserver for BinaryLogObserver {
+    fn on_wait_start(&self, event: &LockEvent<'_>) {
+        self.record(EventKind::WaitStart, event, 0);
+    }
+
+    fn on_acquired(&self, event: &LockEvent<'_>) {
+        let waited = micros(event.waited.unwrap_or_default());
+        self.record(EventKind::Acquired, event, waited);
+    }
+
+    fn on_released(&self, event: &LockEvent<'_>) {
+        let held = micros(event.held.unwrap_or_default());
+        self.record(EventKind::Released, event, held);
+    }
+
+    fn on_poisoned(&self, event: &LockEvent<'_>) {
+        let held = micros(event.held.unwrap_or_default());
+        self.record(EventKind::Poisoned, event, held);
+    }
+
+    fn on_timeout(&self, event: &LockEvent<'_>) {
+        let waited = micros(event.waited.unwrap_or_default());
+        self.record(EventKind::TimedOut, event, waited);
+    }
+}
+
+impl fmt::Debug for BinaryLogObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BinaryLogObserver")
+            .field("start", &self.start)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for BinaryLogObserver {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Reads the records of a binary log back, in the order they were written.
+#[derive(Debug)]
+pub struct Reader<R> {
+    inner: R,
+}
+
+impl Reader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        inner.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a trace-mutex binary log"));
+        }
+        Ok(Self { inner })
+    }
+
+    fn next_record(&mut self) -> io::Result<Option<Record>> {
+        let mut tag = [0];
+        if self.inner.read(&mut tag)? == 0 {
+            return Ok(None);
+        }
+        let record = match tag[0] {
+            TAG_SITE => Record::Site {
+                index: self.u32()?,
+                site: self.string()?,
+            },
+            TAG_LOCK => Record::Lock {
+                id: self.u64()?,
+                name: self.string()?,
+                created: self.u32()?,
+            },
+            TAG_THREAD => Record::Thread {
+                index: self.u32()?,
+                name: self.string()?,
+            },
+            tag => match EventKind::from_tag(tag) {
+                Some(kind) => Record::Event(Event {
+                    kind,
+                    seq: self.u64()?,
+                    ts_us: self.u64()?,
+                    thread: self.u32()?,
+                    lock: self.u64()?,
+                    site: self.u32()?,
+                    duration_us: self.u64()?,
+                }),
+                None => return Err(invalid("unknown record tag")),
+            },
+        };
+        Ok(Some(record))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let mut buf = [0; 4];
+        self.inner.read_exact(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let mut buf = [0; 8];
+        self.inner.read_exact(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    // The length comes from the file, so the buffer grows with the bytes
+    // actually read rather than being allocated up front.
+    fn string(&mut self) -> io::Result<String> {
+        let len = u64::from(self.u32()?);
+        let mut buf = Vec::new();
+        (&mut self.inner).take(len).read_to_end(&mut buf)?;
+        if buf.len() as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        String::from_utf8(buf).map_err(|_| invalid("string is not UTF-8"))
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+fn write_str(writer: &mut impl Write, s: &str) -> io::Result<()> {
+    writer.write_all(&(s.len() as u32).to_le_bytes())?;
+    writer.write_all(s.as_bytes())
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
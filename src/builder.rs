@@ -0,0 +1,66 @@
+use std::{fmt, sync::Arc};
+
+use crate::{state::LockOptions, Mutex, Thresholds, WaitStrategy};
+
+/// What `lock` and friends do when the mutex turns out to be poisoned.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PoisonPolicy {
+    /// Return a `PoisonError`, like `std::sync::Mutex`.
+    #[default]
+    Propagate,
+    /// Hand out the guard as if the mutex were not poisoned.
+    Ignore,
+    /// Panic, naming the critical section that poisoned the mutex.
+    Panic,
+}
+
+/// Configures a `Mutex` individually, instead of with the crate defaults.
+#[derive(Default)]
+pub struct MutexBuilder {
+    options: LockOptions,
+    on_poison: PoisonPolicy,
+}
+
+impl MutexBuilder {
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.options.name = Some(name.into());
+        self
+    }
+
+    pub fn wait_thresholds(mut self, thresholds: Thresholds) -> Self {
+        self.options.wait_thresholds = Some(thresholds);
+        self
+    }
+
+    pub fn hold_thresholds(mut self, thresholds: Thresholds) -> Self {
+        self.options.hold_thresholds = Some(thresholds);
+        self
+    }
+
+    pub fn wait_strategy(mut self, strategy: impl WaitStrategy + 'static) -> Self {
+        self.options.strategy = Some(Arc::new(strategy));
+        self
+    }
+
+    pub fn on_poison(mut self, policy: PoisonPolicy) -> Self {
+        self.on_poison = policy;
+        self
+    }
+
+    #[track_caller]
+    pub fn build<T>(self, data: T) -> Mutex<T> {
+        Mutex::with_options(data, self.options, self.on_poison)
+    }
+}
+
+impl fmt::Debug for MutexBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MutexBuilder")
+            .field("name", &self.options.name)
+            .field("wait_thresholds", &self.options.wait_thresholds)
+            .field("hold_thresholds", &self.options.hold_thresholds)
+            .field("wait_strategy", &self.options.strategy)
+            .field("on_poison", &self.on_poison)
+            .finish()
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{Mutex as StdMutex, MutexGuard as StdMutexGuard, PoisonError as StdPoisonError},
+    thread::ThreadId,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    observer::{LockEvent, LockObserver},
+    stats::micros,
+};
+
+/// Records waits and holds as a Chrome Trace Event Format timeline, for
+/// viewing in Perfetto or `chrome://tracing`.
+///
+/// Install it with `add_observer`, and call `finish` once done recording.
+pub struct ChromeTraceObserver {
+    start: Instant,
+    out: StdMutex<Recording>,
+}
+
+struct Recording {
+    writer: Option<Box<dyn Write + Send>>,
+    // Chrome wants small integers for thread ids; `ThreadId` has no stable
+    // way to provide one.
+    tids: HashMap<ThreadId, usize>,
+    first: bool,
+}
+
+impl ChromeTraceObserver {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            start: Instant::now(),
+            out: StdMutex::new(Recording {
+                writer: Some(Box::new(writer)),
+                tids: HashMap::new(),
+                first: true,
+            }),
+        }
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        match &mut self.out().writer {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Closes the JSON array and stops recording.
+    pub fn finish(&self) -> io::Result<()> {
+        let mut out = self.out();
+        match out.writer.take() {
+            Some(mut writer) => {
+                if out.first {
+                    writer.write_all(b"[")?;
+                }
+                writer.write_all(b"\n]\n")?;
+                writer.flush()
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Writes one complete ("X") event covering `elapsed` up to now.
+    fn record(&self, event: &LockEvent<'_>, phase: &str, elapsed: Duration) {
+        let end = micros(self.start.elapsed());
+        let dur = micros(elapsed);
+        let mut out = self.out();
+        let Recording {
+            writer,
+            tids,
+            first,
+        } = &mut *out;
+        let writer = match writer {
+            Some(writer) => writer,
+            None => return,
+        };
+
+        let mut json = String::new();
+        let next = tids.len() + 1;
+        let tid = *tids.entry(event.thread.id()).or_insert_with(|| {
+            json.push_str(&format!(
+                "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},\"args\":{{\"name\":\"{}\"}}}}",
+                next,
+                escape(&event.thread.name().map_or_else(
+                    || format!("{:?}", event.thread.id()),
+                    str::to_owned
+                ))
+            ));
+            json.push_str(",\n");
+            next
+        });
+        json.push_str(&format!(
+            "{{\"name\":\"{} {}\",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":{},\"args\":{{\"id\":{},\"name\":\"{}\",\"created\":\"{}\",\"site\":\"{}\"}}}}",
+            phase,
+            escape(event.name.unwrap_or(event.ident)),
+            phase,
+            end.saturating_sub(dur),
+            dur,
+            tid,
+            event.id,
+            escape(event.name.unwrap_or_default()),
+            escape(&event.created.to_string()),
+            escape(&event.site.to_string()),
+        ));
+
+        let separator: &[u8] = if *first { b"[\n" } else { b",\n" };
+        *first = false;
+        // A failed write only loses timeline data; the lock itself is fine.
+        let _ = writer
+            .write_all(separator)
+            .and_then(|_| writer.write_all(json.as_bytes()));
+    }
+
+    fn out(&self) -> StdMutexGuard<'_, Recording> {
+        self.out.lock().unwrap_or_else(StdPoisonError::into_inner)
+    }
+}
+
+impl LockObserver for ChromeTraceObserver {
+    // Uncontended acquisitions take a few microseconds too, but are not
+    // worth a slice each.
+    fn on_acquired(&self, event: &LockEvent<'_>) {
+        if event.contended {
+            self.record(event, "wait", event.waited.unwrap_or_default());
+        }
+    }
+
+    fn on_timeout(&self, event: &LockEvent<'_>) {
+        self.record(event, "timeout", event.waited.unwrap_or_default());
+    }
+
+    fn on_released(&self, event: &LockEvent<'_>) {
+        self.record(event, "hold", event.held.unwrap_or_default());
+    }
+}
+
+impl fmt::Debug for ChromeTraceObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChromeTraceObserver")
+            .field("start", &self.start)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for ChromeTraceObserver {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
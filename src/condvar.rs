@@ -0,0 +1,259 @@
+use std::{
+    sync::{
+        atomic::Ordering, Condvar as StdCondvar, Mutex as StdMutex, PoisonError as StdPoisonError,
+        WaitTimeoutResult,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{observer, CallSite, LockResult, MutexGuard, ThreadInfo, MUTEX_ID};
+
+#[derive(Debug)]
+pub struct Condvar {
+    inner: StdCondvar,
+    id: usize,
+    last_notify: StdMutex<Option<(CallSite, Instant)>>,
+}
+
+/// One `Condvar` operation, as reported to a `LockObserver`.
+#[derive(Clone, Debug)]
+pub struct CondvarEvent<'a> {
+    pub(crate) id: usize,
+    pub(crate) mutex: Option<&'a str>,
+    pub(crate) site: CallSite,
+    pub(crate) thread: ThreadInfo,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) waited: Option<Duration>,
+    pub(crate) reacquired: Option<Duration>,
+    pub(crate) notified_at: Option<CallSite>,
+    pub(crate) timed_out: bool,
+    pub(crate) notify_all: bool,
+}
+
+impl<'a> CondvarEvent<'a> {
+    fn new(id: usize, site: CallSite) -> Self {
+        Self {
+            id,
+            mutex: None,
+            site,
+            thread: ThreadInfo::current(),
+            timeout: None,
+            waited: None,
+            reacquired: None,
+            notified_at: None,
+            timed_out: false,
+            notify_all: false,
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The mutex waited with, as log messages name it. `None` for notifies.
+    pub fn mutex(&self) -> Option<&str> {
+        self.mutex
+    }
+
+    /// Where the wait or notify was requested.
+    pub fn site(&self) -> CallSite {
+        self.site
+    }
+
+    pub fn thread(&self) -> &ThreadInfo {
+        &self.thread
+    }
+
+    /// The longest a timed wait may last.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// For wake-ups, how long the thread waited before it was notified or
+    /// timed out, or in all after a spurious wake-up.
+    pub fn wait_time(&self) -> Option<Duration> {
+        self.waited
+    }
+
+    /// For wake-ups by a notify or a timeout, how long it then took to get
+    /// the mutex back.
+    pub fn reacquire_time(&self) -> Option<Duration> {
+        self.reacquired
+    }
+
+    /// For wake-ups, where the notify that woke the thread was sent. `None`
+    /// for timeouts and spurious wake-ups.
+    pub fn notified_at(&self) -> Option<CallSite> {
+        self.notified_at
+    }
+
+    pub fn timed_out(&self) -> bool {
+        self.timed_out
+    }
+
+    /// For notifies, whether every waiter was woken rather than one.
+    pub fn notify_all(&self) -> bool {
+        self.notify_all
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        let id = MUTEX_ID.fetch_add(1, Ordering::AcqRel);
+        Self {
+            inner: StdCondvar::new(),
+            id,
+            last_notify: StdMutex::new(None),
+        }
+    }
+
+    #[track_caller]
+    pub fn wait<'a, T>(&self, mut guard: MutexGuard<'a, T>) -> LockResult<MutexGuard<'a, T>> {
+        let site = CallSite::caller();
+        let start = Instant::now();
+        let event = CondvarEvent {
+            mutex: Some(&guard.id),
+            ..CondvarEvent::new(self.id, site)
+        };
+        observer::notify(|o| o.on_condvar_wait(&event));
+
+        let inner = guard.park();
+        let result = self.inner.wait(inner);
+        let poisoned = result.is_err();
+        let reacquired = self.report_wake(&guard.id, site, start);
+        guard.unpark(
+            result.unwrap_or_else(StdPoisonError::into_inner),
+            reacquired,
+        );
+
+        if poisoned {
+            let lock = guard.lock;
+            lock.poisoned(guard)
+        } else {
+            Ok(guard)
+        }
+    }
+
+    #[track_caller]
+    pub fn wait_while<'a, T, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: F,
+    ) -> LockResult<MutexGuard<'a, T>>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            guard = self.wait(guard)?;
+        }
+        Ok(guard)
+    }
+
+    #[track_caller]
+    pub fn wait_timeout<'a, T>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        dur: Duration,
+    ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)> {
+        let site = CallSite::caller();
+        let start = Instant::now();
+        let event = CondvarEvent {
+            mutex: Some(&guard.id),
+            timeout: Some(dur),
+            ..CondvarEvent::new(self.id, site)
+        };
+        observer::notify(|o| o.on_condvar_wait(&event));
+
+        let inner = guard.park();
+        let result = self.inner.wait_timeout(inner, dur);
+        let poisoned = result.is_err();
+        let (inner, timeout) = result.unwrap_or_else(StdPoisonError::into_inner);
+        let reacquired = if timeout.timed_out() {
+            // Anything past the deadline went into reacquiring the mutex.
+            let now = Instant::now();
+            let reacquired = start
+                .checked_add(dur)
+                .map_or(Duration::from_secs(0), |deadline| {
+                    now.saturating_duration_since(deadline)
+                });
+            let event = CondvarEvent {
+                mutex: Some(&guard.id),
+                timeout: Some(dur),
+                waited: Some(now - start - reacquired),
+                reacquired: Some(reacquired),
+                timed_out: true,
+                ..CondvarEvent::new(self.id, site)
+            };
+            observer::notify(|o| o.on_condvar_wake(&event));
+            reacquired
+        } else {
+            self.report_wake(&guard.id, site, start)
+        };
+        guard.unpark(inner, reacquired);
+
+        if poisoned {
+            let lock = guard.lock;
+            lock.poisoned(guard)
+                .map(|guard| (guard, timeout))
+                .map_err(|err| err.map(|guard| (guard, timeout)))
+        } else {
+            Ok((guard, timeout))
+        }
+    }
+
+    #[track_caller]
+    pub fn notify_one(&self) {
+        let site = CallSite::caller();
+        self.record_notify(site, false);
+        self.inner.notify_one();
+    }
+
+    #[track_caller]
+    pub fn notify_all(&self) {
+        let site = CallSite::caller();
+        self.record_notify(site, true);
+        self.inner.notify_all();
+    }
+
+    fn record_notify(&self, site: CallSite, all: bool) {
+        let event = CondvarEvent {
+            notify_all: all,
+            ..CondvarEvent::new(self.id, site)
+        };
+        observer::notify(|o| o.on_condvar_notify(&event));
+        let mut last = self
+            .last_notify
+            .lock()
+            .unwrap_or_else(StdPoisonError::into_inner);
+        *last = Some((site, Instant::now()));
+    }
+
+    // std hands the mutex back already reacquired, so split the time at the
+    // most recent notify: before it we were waiting, after it reacquiring.
+    // Returns the time spent reacquiring.
+    fn report_wake(&self, ident: &str, site: CallSite, start: Instant) -> Duration {
+        let now = Instant::now();
+        let last = *self
+            .last_notify
+            .lock()
+            .unwrap_or_else(StdPoisonError::into_inner);
+        let mut event = CondvarEvent {
+            mutex: Some(ident),
+            waited: Some(now - start),
+            ..CondvarEvent::new(self.id, site)
+        };
+        if let Some((notified_at, at)) = last.filter(|&(_, at)| at >= start) {
+            event.waited = Some(at - start);
+            event.reacquired = Some(now - at);
+            event.notified_at = Some(notified_at);
+        }
+        observer::notify(|o| o.on_condvar_wake(&event));
+        event.reacquired.unwrap_or_default()
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex as StdMutex, PoisonError as StdPoisonError,
+    },
+    thread::{self, ThreadId},
+    time::Instant,
+};
+
+use crate::{
+    observer,
+    state::{LockInfo, LockState},
+    CallSite, Holder, ThreadInfo, Waiter,
+};
+
+static PANIC_ON_DEADLOCK: AtomicBool = AtomicBool::new(false);
+
+static WAIT_FOR: StdMutex<Option<WaitFor>> = StdMutex::new(None);
+
+// Which lock every contended thread is waiting on. Together with each lock's
+// holder this is the wait-for graph.
+#[derive(Default)]
+struct WaitFor {
+    waiters: HashMap<ThreadId, Entry>,
+    reported: HashSet<Vec<usize>>,
+}
+
+struct Entry {
+    lock: Arc<LockState>,
+    waiter: Waiter,
+}
+
+/// Panic in the waiting thread once it is found to be part of a deadlock,
+/// instead of only logging the cycle.
+pub fn set_panic_on_deadlock(enabled: bool) {
+    PANIC_ON_DEADLOCK.store(enabled, Ordering::Release);
+}
+
+// Removes the thread from the wait-for graph when dropped.
+pub(crate) struct Waiting(ThreadId);
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        if let Some(wait_for) = wait_for().as_mut() {
+            wait_for.waiters.remove(&self.0);
+        }
+    }
+}
+
+pub(crate) fn start_waiting(lock: &Arc<LockState>, site: CallSite, since: Instant) -> Waiting {
+    let thread = ThreadInfo::current();
+    let me = thread.id();
+    wait_for()
+        .get_or_insert_with(WaitFor::default)
+        .waiters
+        .insert(
+            me,
+            Entry {
+                lock: Arc::clone(lock),
+                waiter: Waiter {
+                    thread,
+                    site,
+                    since,
+                },
+            },
+        );
+    Waiting(me)
+}
+
+#[cfg(feature = "registry")]
+pub(crate) fn waiters_of(id: usize) -> Vec<Waiter> {
+    wait_for().as_ref().map_or_else(Vec::new, |wait_for| {
+        wait_for
+            .waiters
+            .values()
+            .filter(|entry| entry.lock.id == id)
+            .map(|entry| entry.waiter.clone())
+            .collect()
+    })
+}
+
+/// A cycle of threads, each waiting for a lock the next one holds.
+#[derive(Clone, Debug)]
+pub struct Deadlock {
+    links: Vec<DeadlockLink>,
+}
+
+/// One thread of a deadlock and the lock it is stuck on.
+#[derive(Clone, Debug)]
+pub struct DeadlockLink {
+    lock: LockInfo,
+    waiter: Waiter,
+    holder: Holder,
+}
+
+impl Deadlock {
+    /// In cycle order: each link's holder is the next link's waiter.
+    pub fn links(&self) -> &[DeadlockLink] {
+        &self.links
+    }
+}
+
+impl DeadlockLink {
+    pub fn lock(&self) -> &LockInfo {
+        &self.lock
+    }
+
+    pub fn waiter(&self) -> &Waiter {
+        &self.waiter
+    }
+
+    pub fn holder(&self) -> &Holder {
+        &self.holder
+    }
+}
+
+impl fmt::Display for Deadlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Deadlock detected:")?;
+        for link in &self.links {
+            write!(
+                f,
+                "\n    thread {} waits for {} at {}, held by thread {} since {}",
+                link.waiter.thread,
+                link.lock,
+                link.waiter.site,
+                link.holder.thread,
+                link.holder.site
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Follow waiter -> lock -> holder -> waiter ... from the current thread and
+// report if it leads back to us.
+pub(crate) fn check() {
+    if let Some(deadlock) = find(thread::current().id()) {
+        observer::notify(|o| o.on_deadlock(&deadlock));
+        if PANIC_ON_DEADLOCK.load(Ordering::Acquire) {
+            panic!("{}", deadlock);
+        }
+    }
+}
+
+// Like `check`, on behalf of a thread blocked in the underlying lock, which
+// cannot be made to panic.
+pub(crate) fn check_blocked(thread: ThreadId) {
+    if let Some(deadlock) = find(thread) {
+        observer::notify(|o| o.on_deadlock(&deadlock));
+    }
+}
+
+// A not yet reported cycle through `me`, if there is one.
+fn find(me: ThreadId) -> Option<Deadlock> {
+    let mut wait_for = wait_for();
+    let wait_for = wait_for.as_mut()?;
+
+    let mut links = Vec::new();
+    let mut current = me;
+    loop {
+        let entry = wait_for.waiters.get(&current)?;
+        let holder = entry.lock.holder.get()?;
+        if links.len() > wait_for.waiters.len() {
+            // A cycle that we are waiting on but not part of; its own
+            // members will report it.
+            return None;
+        }
+        current = holder.thread.id();
+        links.push(DeadlockLink {
+            lock: entry.lock.info(),
+            waiter: entry.waiter.clone(),
+            holder,
+        });
+        if current == me {
+            break;
+        }
+    }
+
+    let mut locks: Vec<usize> = links.iter().map(|link| link.lock.id).collect();
+    locks.sort_unstable();
+    if !wait_for.reported.insert(locks) {
+        return None;
+    }
+    Some(Deadlock { links })
+}
+
+fn wait_for() -> std::sync::MutexGuard<'static, Option<WaitFor>> {
+    WAIT_FOR.lock().unwrap_or_else(StdPoisonError::into_inner)
+}
@@ -0,0 +1,280 @@
+use std::{error::Error, fmt, sync::Arc, time::Duration};
+
+use crate::{state::Label, CallSite, ThreadInfo};
+
+pub type LockResult<G> = Result<G, PoisonError<G>>;
+pub type TryLockResult<G> = Result<G, TryLockError<G>>;
+
+/// The critical section that panicked and poisoned a lock.
+#[derive(Clone, Debug)]
+pub struct PoisonInfo {
+    pub(crate) thread: ThreadInfo,
+    pub(crate) site: CallSite,
+    pub(crate) held: Duration,
+}
+
+impl PoisonInfo {
+    pub fn thread(&self) -> &ThreadInfo {
+        &self.thread
+    }
+
+    pub fn site(&self) -> CallSite {
+        self.site
+    }
+
+    pub fn held(&self) -> Duration {
+        self.held
+    }
+}
+
+impl fmt::Display for PoisonInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "poisoned by lock at {} on thread {} after {:?}",
+            self.site, self.thread, self.held
+        )
+    }
+}
+
+/// Like `std::sync::PoisonError`, but the guard inside is still traced.
+pub struct PoisonError<T> {
+    guard: T,
+    poisoned_by: Option<Box<PoisonInfo>>,
+}
+
+impl<T> PoisonError<T> {
+    pub fn new(guard: T) -> Self {
+        Self {
+            guard,
+            poisoned_by: None,
+        }
+    }
+
+    pub(crate) fn with_info(guard: T, poisoned_by: Option<PoisonInfo>) -> Self {
+        Self {
+            guard,
+            poisoned_by: poisoned_by.map(Box::new),
+        }
+    }
+
+    pub(crate) fn map<U>(self, f: impl FnOnce(T) -> U) -> PoisonError<U> {
+        PoisonError {
+            guard: f(self.guard),
+            poisoned_by: self.poisoned_by,
+        }
+    }
+
+    pub fn poisoned_by(&self) -> Option<&PoisonInfo> {
+        self.poisoned_by.as_deref()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.guard
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.guard
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> fmt::Debug for PoisonError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoisonError")
+            .field("poisoned_by", &self.poisoned_by)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for PoisonError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("poisoned lock: another task failed inside")?;
+        match &self.poisoned_by {
+            Some(info) => write!(f, " ({})", info),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T> Error for PoisonError<T> {}
+
+pub enum TryLockError<T> {
+    Poisoned(PoisonError<T>),
+    WouldBlock,
+}
+
+impl<T> From<PoisonError<T>> for TryLockError<T> {
+    fn from(err: PoisonError<T>) -> Self {
+        TryLockError::Poisoned(err)
+    }
+}
+
+impl<T> fmt::Debug for TryLockError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryLockError::Poisoned(..) => f.write_str("Poisoned(..)"),
+            TryLockError::WouldBlock => f.write_str("WouldBlock"),
+        }
+    }
+}
+
+impl<T> fmt::Display for TryLockError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryLockError::Poisoned(p) => p.fmt(f),
+            TryLockError::WouldBlock => {
+                f.write_str("try_lock failed because the operation would block")
+            }
+        }
+    }
+}
+
+impl<T> Error for TryLockError<T> {}
+
+/// A timed acquisition gave up before the lock became free.
+#[derive(Debug, Clone)]
+pub struct LockTimeout {
+    pub(crate) id: usize,
+    pub(crate) name: Option<Arc<str>>,
+    pub(crate) created: CallSite,
+    pub(crate) site: CallSite,
+    pub(crate) waited: Duration,
+}
+
+impl LockTimeout {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Where the lock was created.
+    pub fn created(&self) -> CallSite {
+        self.created
+    }
+
+    pub fn site(&self) -> CallSite {
+        self.site
+    }
+
+    pub fn waited(&self) -> Duration {
+        self.waited
+    }
+}
+
+impl fmt::Display for LockTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at {} timed out after {:?}",
+            mutex_label(self.id, self.name.as_deref(), self.created),
+            self.site,
+            self.waited
+        )
+    }
+}
+
+impl Error for LockTimeout {}
+
+/// A thread tried to lock a `Mutex` it already holds.
+#[derive(Debug, Clone)]
+pub struct ReentrantLock {
+    pub(crate) id: usize,
+    pub(crate) name: Option<Arc<str>>,
+    pub(crate) created: CallSite,
+    pub(crate) thread: ThreadInfo,
+    pub(crate) held_at: CallSite,
+    pub(crate) requested_at: CallSite,
+}
+
+impl ReentrantLock {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Where the lock was created.
+    pub fn created(&self) -> CallSite {
+        self.created
+    }
+
+    pub fn thread(&self) -> &ThreadInfo {
+        &self.thread
+    }
+
+    pub fn held_at(&self) -> CallSite {
+        self.held_at
+    }
+
+    pub fn requested_at(&self) -> CallSite {
+        self.requested_at
+    }
+}
+
+impl fmt::Display for ReentrantLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} requested at {} by thread {}, which already holds it since {}",
+            mutex_label(self.id, self.name.as_deref(), self.created),
+            self.requested_at,
+            self.thread,
+            self.held_at
+        )
+    }
+}
+
+impl Error for ReentrantLock {}
+
+pub enum TimedLockError<G> {
+    Timeout(LockTimeout),
+    Reentrant(ReentrantLock),
+    Poisoned(PoisonError<G>),
+}
+
+impl<G> From<PoisonError<G>> for TimedLockError<G> {
+    fn from(err: PoisonError<G>) -> Self {
+        TimedLockError::Poisoned(err)
+    }
+}
+
+impl<G> fmt::Debug for TimedLockError<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimedLockError::Timeout(t) => f.debug_tuple("Timeout").field(t).finish(),
+            TimedLockError::Reentrant(r) => f.debug_tuple("Reentrant").field(r).finish(),
+            TimedLockError::Poisoned(..) => f.write_str("Poisoned(..)"),
+        }
+    }
+}
+
+impl<G> fmt::Display for TimedLockError<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimedLockError::Timeout(t) => t.fmt(f),
+            TimedLockError::Reentrant(r) => r.fmt(f),
+            TimedLockError::Poisoned(p) => p.fmt(f),
+        }
+    }
+}
+
+impl<G> Error for TimedLockError<G> {}
+
+// Timeouts and reentrancy are only reported for `Mutex`.
+fn mutex_label(id: usize, name: Option<&str>, created: CallSite) -> Label<'_> {
+    Label {
+        kind: "Mutex",
+        id,
+        name,
+        created,
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    fmt,
+    sync::{Mutex as StdMutex, MutexGuard as StdMutexGuard, PoisonError as StdPoisonError},
+    time::Instant,
+};
+
+use crate::{CallSite, ThreadInfo};
+
+/// Who currently holds a lock, and since when.
+#[derive(Clone, Debug)]
+pub struct Holder {
+    pub(crate) thread: ThreadInfo,
+    pub(crate) site: CallSite,
+    pub(crate) since: Instant,
+}
+
+impl Holder {
+    pub fn thread(&self) -> &ThreadInfo {
+        &self.thread
+    }
+
+    pub fn site(&self) -> CallSite {
+        self.site
+    }
+
+    pub fn since(&self) -> Instant {
+        self.since
+    }
+}
+
+impl fmt::Display for Holder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "held by thread {} since {} ({:?})",
+            self.thread,
+            self.site,
+            self.since.elapsed()
+        )
+    }
+}
+
+/// A thread blocked waiting for a lock.
+#[derive(Clone, Debug)]
+pub struct Waiter {
+    pub(crate) thread: ThreadInfo,
+    pub(crate) site: CallSite,
+    pub(crate) since: Instant,
+}
+
+impl Waiter {
+    pub fn thread(&self) -> &ThreadInfo {
+        &self.thread
+    }
+
+    pub fn site(&self) -> CallSite {
+        self.site
+    }
+
+    pub fn since(&self) -> Instant {
+        self.since
+    }
+}
+
+impl fmt::Display for Waiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "thread {} waiting at {} ({:?})",
+            self.thread,
+            self.site,
+            self.since.elapsed()
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct HolderSlot {
+    inner: StdMutex<Option<Holder>>,
+}
+
+impl HolderSlot {
+    pub(crate) fn set(&self, site: CallSite, since: Instant) {
+        *self.slot() = Some(Holder {
+            thread: ThreadInfo::current(),
+            site,
+            since,
+        });
+    }
+
+    pub(crate) fn clear(&self) {
+        *self.slot() = None;
+    }
+
+    pub(crate) fn get(&self) -> Option<Holder> {
+        self.slot().clone()
+    }
+
+    fn slot(&self) -> StdMutexGuard<'_, Option<Holder>> {
+        self.inner.lock().unwrap_or_else(StdPoisonError::into_inner)
+    }
+}
@@ -1,133 +1,561 @@
 use std::{
+    fmt,
     ops::{Deref, DerefMut},
+    panic::Location,
     sync::{
-        atomic::{AtomicUsize, Ordering},
-        Mutex as StdMutex, MutexGuard as StdMutexGuard, PoisonError as StdPoisonError,
-        TryLockError as StdTryLockError,
+        atomic::AtomicUsize, Arc, Mutex as StdMutex, MutexGuard as StdMutexGuard,
+        PoisonError as StdPoisonError, TryLockError as StdTryLockError,
     },
-    thread::sleep,
+    thread::{self, sleep},
     time::{Duration, Instant},
 };
 
-use log::{debug, error, info, trace, warn};
+use crate::{
+    backtrace::Trace,
+    progress::ReportSchedule,
+    span::LockSpan,
+    state::{LockOptions, LockState},
+};
+
+// The `backtrace` feature needs std's `Backtrace`, stable since 1.65.
+#[clippy::msrv = "1.65"]
+mod backtrace;
+#[cfg(feature = "binlog")]
+pub mod binlog;
+mod builder;
+#[cfg(feature = "chrome-trace")]
+mod chrome_trace;
+mod condvar;
+mod deadlock;
+mod error;
+mod holder;
+#[cfg(feature = "lockdep")]
+mod lockdep;
+mod observer;
+mod progress;
+#[cfg(feature = "prometheus")]
+mod prometheus;
+#[cfg(feature = "registry")]
+pub mod registry;
+mod rwlock;
+mod site;
+mod span;
+mod state;
+mod stats;
+mod strategy;
+mod thread_info;
+mod thresholds;
+#[cfg(feature = "watchdog")]
+mod watchdog;
 
-#[cfg(feature = "1_46_0")]
-use std::panic::Location;
+#[cfg(feature = "backtrace")]
+pub use backtrace::set_backtrace_threshold;
+#[cfg(feature = "binlog")]
+pub use binlog::BinaryLogObserver;
+pub use builder::{MutexBuilder, PoisonPolicy};
+#[cfg(feature = "chrome-trace")]
+pub use chrome_trace::ChromeTraceObserver;
+pub use condvar::{Condvar, CondvarEvent};
+pub use deadlock::{set_panic_on_deadlock, Deadlock, DeadlockLink};
+pub use error::{
+    LockResult, LockTimeout, PoisonError, PoisonInfo, ReentrantLock, TimedLockError, TryLockError,
+    TryLockResult,
+};
+pub use holder::{Holder, Waiter};
+#[cfg(feature = "lockdep")]
+pub use lockdep::{LockOrder, LockOrderInversion};
+pub use observer::{add_observer, set_observers, LockEvent, LockObserver, LogObserver};
+#[cfg(feature = "prometheus")]
+pub use prometheus::PrometheusObserver;
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use site::CallSite;
+pub use state::LockInfo;
+pub use stats::LockStats;
+pub use strategy::{Blocking, ExponentialBackoff, Pause, SpinThenBlock, WaitStrategy, YieldSpin};
+pub use thread_info::ThreadInfo;
+pub use thresholds::Thresholds;
+#[cfg(feature = "watchdog")]
+pub use watchdog::{Stall, Watchdog, WatchdogConfig};
 
 const DEFAULT_SPIN: usize = 100;
-const SPIN_INCREASE: usize = 2;
-const DEBUG_THRESHOLD: usize = 50_000;
-const INFO_THRESHOLD: usize = 500_000;
-const WARN_THRESHOLD: usize = 3_000_000;
-const ERROR_THRESHOLD: usize = 60_000_000;
+// How often a timed acquisition that would rather block checks its deadline.
+const BLOCK_POLL: Duration = Duration::from_millis(1);
 
 static MUTEX_ID: AtomicUsize = AtomicUsize::new(0);
 
 #[derive(Debug)]
 pub struct Mutex<T> {
     inner: StdMutex<T>,
-    spin_us: AtomicUsize,
-    id: usize,
+    state: Arc<LockState>,
+    poisoned_by: StdMutex<Option<PoisonInfo>>,
+    on_poison: PoisonPolicy,
 }
 
 pub struct MutexGuard<'a, T> {
-    inner: StdMutexGuard<'a, T>,
+    // Only vacated while a `Condvar` is waiting with this guard.
+    inner: Option<StdMutexGuard<'a, T>>,
+    lock: &'a Mutex<T>,
     id: String,
+    site: CallSite,
+    acquired: Instant,
+    // Like std's poison flag: only a panic that starts while the guard is
+    // held poisons the lock.
+    panicking: bool,
+    trace: Trace,
+    span: LockSpan,
+}
+
+impl<'a, T> MutexGuard<'a, T> {
+    fn new(lock: &'a Mutex<T>, inner: StdMutexGuard<'a, T>, id: String, site: CallSite) -> Self {
+        let acquired = Instant::now();
+        lock.state.held(site, acquired);
+        Self {
+            inner: Some(inner),
+            lock,
+            id,
+            site,
+            acquired,
+            panicking: thread::panicking(),
+            trace: Trace::capture(),
+            span: LockSpan::holding(&lock.state, site),
+        }
+    }
+
+    // Ends the hold so far and hands out the std guard, for a `Condvar` to
+    // wait with.
+    fn park(&mut self) -> StdMutexGuard<'a, T> {
+        let inner = self.inner.take().expect("guard already waiting");
+        let held = self.acquired.elapsed();
+        let state = &self.lock.state;
+        state.released();
+        state.stats.record_release(held);
+        let event = LockEvent::new(state, &self.id, self.site).held(held);
+        observer::notify(|o| o.on_released(&event));
+        self.trace.report(&event);
+        self.span.held(held);
+        inner
+    }
+
+    // Starts a new hold once a `Condvar` has reacquired the mutex, which
+    // took `reacquired` after the wake-up.
+    fn unpark(&mut self, inner: StdMutexGuard<'a, T>, reacquired: Duration) {
+        let state = &self.lock.state;
+        self.inner = Some(inner);
+        self.acquired = Instant::now();
+        state.held(self.site, self.acquired);
+        state.stats.record_acquire(reacquired, false);
+        let event = LockEvent::new(state, &self.id, self.site).waited(reacquired);
+        observer::notify(|o| o.on_acquired(&event));
+        self.trace = Trace::capture();
+        self.span = LockSpan::holding(state, self.site);
+    }
 }
 
 impl<'a, T> Deref for MutexGuard<'a, T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
-        self.inner.deref()
+        self.inner.as_ref().expect("guard is waiting on a Condvar")
     }
 }
 
 impl<'a, T> DerefMut for MutexGuard<'a, T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        self.inner.deref_mut()
+        self.inner.as_mut().expect("guard is waiting on a Condvar")
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for MutexGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
     }
 }
 
 impl<'a, T> Drop for MutexGuard<'a, T> {
     fn drop(&mut self) {
-        trace!("{} - Released", self.id);
+        // The std guard is dropped after us and poisons the mutex, so this
+        // is our last chance to say who did it.
+        let held = self.acquired.elapsed();
+        if !self.panicking && thread::panicking() {
+            let info = PoisonInfo {
+                thread: ThreadInfo::current(),
+                site: self.site,
+                held,
+            };
+            self.lock.state.stats.record_poison();
+            let event = LockEvent::new(&self.lock.state, &self.id, self.site).held(held);
+            observer::notify(|o| o.on_poisoned(&event));
+            *self.lock.poison_info() = Some(info);
+        }
+        self.lock.state.released();
+        self.lock.state.stats.record_release(held);
+        let event = LockEvent::new(&self.lock.state, &self.id, self.site).held(held);
+        observer::notify(|o| o.on_released(&event));
+        self.trace.report(&event);
+        self.span.held(held);
+    }
+}
+
+// Not generic, so `Mutex::builder()` needs no type annotation; `build`
+// picks the type from its argument.
+impl Mutex<()> {
+    pub fn builder() -> MutexBuilder {
+        MutexBuilder::default()
     }
 }
 
 impl<T> Mutex<T> {
+    #[track_caller]
     pub fn new(data: T) -> Self {
-        let id = MUTEX_ID.fetch_add(1, Ordering::AcqRel);
+        Self::with_options(data, LockOptions::default(), PoisonPolicy::default())
+    }
+
+    #[track_caller]
+    pub fn named(name: impl Into<String>, data: T) -> Self {
+        Mutex::builder().name(name).build(data)
+    }
+
+    #[track_caller]
+    pub(crate) fn with_options(data: T, options: LockOptions, on_poison: PoisonPolicy) -> Self {
         Self {
             inner: StdMutex::new(data),
-            spin_us: AtomicUsize::new(DEFAULT_SPIN),
-            id,
+            state: LockState::new("Mutex", options),
+            poisoned_by: StdMutex::new(None),
+            on_poison,
         }
     }
 
-    #[cfg_attr(feature = "1_46_0", track_caller)]
-    pub fn lock(&self) -> std::result::Result<MutexGuard<T>, StdPoisonError<StdMutexGuard<T>>> {
+    /// Panics if the calling thread already holds the lock; use
+    /// `try_lock_for` or `try_lock_until` to get a `TimedLockError` instead.
+    #[track_caller]
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
         let start = Instant::now();
-        #[cfg(feature = "1_46_0")]
-        let ident = {
-            let loc = Location::caller();
-            print_id(&loc, self.id)
-        };
-
-        #[cfg(not(feature = "1_46_0"))]
-        let ident = { print_id(self.id) };
-
-        loop {
-            match self.inner.try_lock() {
-                Ok(guard) => {
-                    self.spin_us.store(DEFAULT_SPIN, Ordering::Release);
-                    trace!("{} - Locked", ident);
-                    return Ok(MutexGuard {
-                        inner: guard,
-                        id: ident,
-                    });
+        let site = CallSite::caller();
+        let ident = print_id(&self.state);
+
+        match acquire(
+            &self.state,
+            &ident,
+            site,
+            start,
+            None,
+            || self.inner.try_lock(),
+            || self.inner.lock(),
+        ) {
+            Ok(guard) => Ok(MutexGuard::new(self, guard, ident, site)),
+            Err(AcquireError::Poisoned(p)) => {
+                self.poisoned(MutexGuard::new(self, p.into_inner(), ident, site))
+            }
+            Err(AcquireError::Reentrant(holder)) => {
+                panic!("{}", self.reentrant(&ident, holder, site))
+            }
+            Err(AcquireError::TimedOut) => unreachable!("lock without a deadline gave up"),
+        }
+    }
+
+    #[track_caller]
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        let site = CallSite::caller();
+        let ident = print_id(&self.state);
+
+        match self.inner.try_lock() {
+            Ok(guard) => {
+                self.state
+                    .stats
+                    .record_acquire(Duration::from_secs(0), false);
+                let event =
+                    LockEvent::new(&self.state, &ident, site).waited(Duration::from_secs(0));
+                observer::notify(|o| o.on_acquired(&event));
+                Ok(MutexGuard::new(self, guard, ident, site))
+            }
+            Err(StdTryLockError::WouldBlock) => {
+                self.state.stats.record_try_failure();
+                let event =
+                    LockEvent::new(&self.state, &ident, site).holder(self.state.holder.get());
+                observer::notify(|o| o.on_try_failed(&event));
+                Err(TryLockError::WouldBlock)
+            }
+            Err(StdTryLockError::Poisoned(p)) => {
+                self.state
+                    .stats
+                    .record_acquire(Duration::from_secs(0), false);
+                let event =
+                    LockEvent::new(&self.state, &ident, site).waited(Duration::from_secs(0));
+                observer::notify(|o| o.on_acquired(&event));
+                self.poisoned(MutexGuard::new(self, p.into_inner(), ident, site))
+                    .map_err(TryLockError::Poisoned)
+            }
+        }
+    }
+
+    /// Like `lock`, but gives up after `timeout`, and returns an error
+    /// instead of panicking if the calling thread already holds the lock.
+    /// A timeout too large to represent waits without a deadline.
+    #[track_caller]
+    pub fn try_lock_for(
+        &self,
+        timeout: Duration,
+    ) -> std::result::Result<MutexGuard<'_, T>, TimedLockError<MutexGuard<'_, T>>> {
+        self.lock_until(Instant::now().checked_add(timeout))
+    }
+
+    /// Like `try_lock_for`, with the deadline given as an `Instant`.
+    #[track_caller]
+    pub fn try_lock_until(
+        &self,
+        deadline: Instant,
+    ) -> std::result::Result<MutexGuard<'_, T>, TimedLockError<MutexGuard<'_, T>>> {
+        self.lock_until(Some(deadline))
+    }
+
+    #[track_caller]
+    fn lock_until(
+        &self,
+        deadline: Option<Instant>,
+    ) -> std::result::Result<MutexGuard<'_, T>, TimedLockError<MutexGuard<'_, T>>> {
+        let start = Instant::now();
+        let site = CallSite::caller();
+        let ident = print_id(&self.state);
+
+        match acquire(
+            &self.state,
+            &ident,
+            site,
+            start,
+            deadline,
+            || self.inner.try_lock(),
+            || self.inner.lock(),
+        ) {
+            Ok(guard) => Ok(MutexGuard::new(self, guard, ident, site)),
+            Err(AcquireError::Poisoned(p)) => self
+                .poisoned(MutexGuard::new(self, p.into_inner(), ident, site))
+                .map_err(TimedLockError::Poisoned),
+            Err(AcquireError::Reentrant(holder)) => Err(TimedLockError::Reentrant(
+                self.reentrant(&ident, holder, site),
+            )),
+            Err(AcquireError::TimedOut) => Err(TimedLockError::Timeout(LockTimeout {
+                id: self.state.id,
+                name: self.state.name.clone(),
+                created: self.state.created,
+                site,
+                waited: start.elapsed(),
+            })),
+        }
+    }
+
+    pub fn wait_strategy(&self) -> Arc<dyn WaitStrategy> {
+        self.state.strategy()
+    }
+
+    pub fn set_wait_strategy(&self, strategy: impl WaitStrategy + 'static) {
+        self.state.set_strategy(Arc::new(strategy));
+    }
+
+    /// Where the lock was created, which identifies its lock class.
+    pub fn created(&self) -> CallSite {
+        self.state.created
+    }
+
+    pub fn stats(&self) -> LockStats {
+        self.state.stats()
+    }
+
+    pub fn holder(&self) -> Option<Holder> {
+        self.state.holder.get()
+    }
+
+    pub fn reset_stats(&self) {
+        self.state.stats.reset();
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.inner.is_poisoned()
+    }
+
+    #[cfg(feature = "1_77_0")]
+    #[clippy::msrv = "1.77"]
+    #[track_caller]
+    pub fn clear_poison(&self) {
+        if self.inner.is_poisoned() {
+            let ident = print_id(&self.state);
+            let event = LockEvent::new(&self.state, &ident, CallSite::caller());
+            observer::notify(|o| o.on_poison_cleared(&event));
+        }
+        *self.poison_info() = None;
+        self.inner.clear_poison();
+    }
+
+    pub fn poisoned_by(&self) -> Option<PoisonInfo> {
+        self.poison_info().clone()
+    }
+
+    pub fn into_inner(self) -> LockResult<T> {
+        let poisoned_by = self.poisoned_by();
+        self.inner
+            .into_inner()
+            .map_err(|p| PoisonError::with_info(p.into_inner(), poisoned_by))
+    }
+
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let poisoned_by = self.poisoned_by();
+        self.inner
+            .get_mut()
+            .map_err(|p| PoisonError::with_info(p.into_inner(), poisoned_by))
+    }
+
+    fn poison_info(&self) -> StdMutexGuard<'_, Option<PoisonInfo>> {
+        self.poisoned_by
+            .lock()
+            .unwrap_or_else(StdPoisonError::into_inner)
+    }
+
+    fn reentrant(&self, ident: &str, holder: Holder, site: CallSite) -> ReentrantLock {
+        let event = LockEvent::new(&self.state, ident, site).holder(Some(holder.clone()));
+        observer::notify(|o| o.on_reentry(&event));
+        ReentrantLock {
+            id: self.state.id,
+            name: self.state.name.clone(),
+            created: self.state.created,
+            thread: holder.thread,
+            held_at: holder.site,
+            requested_at: site,
+        }
+    }
+
+    // Applies the poison policy to a guard of the poisoned mutex.
+    #[track_caller]
+    fn poisoned<'a>(&self, guard: MutexGuard<'a, T>) -> LockResult<MutexGuard<'a, T>> {
+        let poisoned_by = self.poisoned_by();
+        let event =
+            LockEvent::new(&self.state, &guard.id, guard.site).poisoned_by(poisoned_by.clone());
+        observer::notify(|o| o.on_poison_found(&event, self.on_poison));
+        match self.on_poison {
+            PoisonPolicy::Propagate => Err(PoisonError::with_info(guard, poisoned_by)),
+            PoisonPolicy::Ignore => Ok(guard),
+            PoisonPolicy::Panic => {
+                let message = match &poisoned_by {
+                    Some(info) => format!("{} - Lock is {}", guard.id, info),
+                    None => format!("{} - Lock is poisoned", guard.id),
+                };
+                // Released first, so the panic does not count as poisoning
+                // the mutex all over again.
+                drop(guard);
+                panic!("{}", message)
+            }
+        }
+    }
+}
+
+enum AcquireError<G> {
+    Poisoned(StdPoisonError<G>),
+    TimedOut,
+    // The lock is held by the very thread waiting for it.
+    Reentrant(Holder),
+}
+
+// Shared by every lock type: retry until `try_lock` succeeds, pausing as the
+// lock's `WaitStrategy` says after each miss, or hand over to the blocking
+// `lock`. Tells the observers as we go.
+fn acquire<G>(
+    state: &Arc<LockState>,
+    ident: &str,
+    site: CallSite,
+    start: Instant,
+    deadline: Option<Instant>,
+    mut try_lock: impl FnMut() -> Result<G, StdTryLockError<G>>,
+    lock: impl FnOnce() -> Result<G, StdPoisonError<G>>,
+) -> Result<G, AcquireError<G>> {
+    let LockState { stats, holder, .. } = &**state;
+    let mut contended = false;
+    let mut _waiting = None;
+    let mut trace = Trace::default();
+    let mut strategy = None;
+    let mut attempt = 0;
+    let mut reports = ReportSchedule::new(state);
+    let span = LockSpan::waiting(state, site);
+    let _entered = span.enter();
+    let result = loop {
+        match try_lock() {
+            Ok(guard) => break Ok(guard),
+            Err(StdTryLockError::Poisoned(p)) => break Err(p),
+            Err(StdTryLockError::WouldBlock) => {
+                if !contended {
+                    let me = thread::current().id();
+                    if let Some(holder) = holder.get().filter(|h| h.thread.id() == me) {
+                        stats.record_failed_wait(start.elapsed(), attempt);
+                        return Err(AcquireError::Reentrant(holder));
+                    }
+                    _waiting = Some(deadlock::start_waiting(state, site, start));
+                    trace = Trace::capture();
+                    let event = LockEvent::new(state, ident, site)
+                        .waited(start.elapsed())
+                        .holder(holder.get());
+                    observer::notify(|o| o.on_wait_start(&event));
                 }
-                Err(StdTryLockError::WouldBlock) => {
-                    let spin = loop {
-                        let load = self.spin_us.load(Ordering::Acquire);
-                        let store = load.saturating_mul(SPIN_INCREASE);
-                        match self.spin_us.compare_exchange(
-                            load,
-                            store,
-                            Ordering::SeqCst,
-                            Ordering::SeqCst,
-                        ) {
-                            Ok(spin) => break spin,
-                            Err(_) => {}
+                contended = true;
+                let remaining = match deadline {
+                    Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                        Some(remaining) if remaining > Duration::from_secs(0) => Some(remaining),
+                        _ => {
+                            let waited = start.elapsed();
+                            stats.record_failed_wait(waited, attempt);
+                            stats.record_timeout();
+                            let event = LockEvent::new(state, ident, site)
+                                .waited(waited)
+                                .holder(holder.get());
+                            observer::notify(|o| o.on_timeout(&event));
+                            return Err(AcquireError::TimedOut);
                         }
-                    };
+                    },
+                    None => None,
+                };
 
-                    match spin {
-                        n if n < DEBUG_THRESHOLD => {}
-                        n if n < INFO_THRESHOLD => {
-                            debug!("{} - Waiting {:?}", ident, start.elapsed())
-                        }
-                        n if n < WARN_THRESHOLD => {
-                            info!("{} - Waiting {:?}", ident, start.elapsed())
-                        }
-                        n if n < ERROR_THRESHOLD => {
-                            warn!("{} - Waiting {:?}", ident, start.elapsed())
-                        }
-                        _ => error!("{} - Waiting {:?}", ident, start.elapsed()),
+                let waited = start.elapsed();
+                if reports.due(waited) {
+                    let event = LockEvent::new(state, ident, site)
+                        .waited(waited)
+                        .holder(holder.get());
+                    observer::notify(|o| o.on_still_waiting(&event));
+                    trace.report(&event);
+                    if waited >= state.deadlock_check_after() {
+                        deadlock::check();
                     }
-                    sleep(Duration::from_micros(spin as u64));
                 }
-                Err(StdTryLockError::Poisoned(p)) => return Err(p),
+
+                let strategy = strategy.get_or_insert_with(|| state.strategy());
+                match strategy.pause(attempt, waited) {
+                    Pause::Sleep(nap) => {
+                        let nap = nap.min(reports.until_due(waited));
+                        sleep(remaining.map_or(nap, |remaining| remaining.min(nap)))
+                    }
+                    Pause::Yield => thread::yield_now(),
+                    Pause::Spin => std::hint::spin_loop(),
+                    Pause::Block => match remaining {
+                        Some(remaining) => sleep(remaining.min(BLOCK_POLL)),
+                        None => {
+                            let _reporting = progress::start(state, ident, site, start);
+                            break lock();
+                        }
+                    },
+                }
+                attempt = attempt.saturating_add(1);
             }
         }
-    }
-}
+    };
 
-#[cfg(not(feature = "1_46_0"))]
-fn print_id(id: usize) -> String {
-    format!("Mutex id: {}", id)
+    let waited = start.elapsed();
+    stats.record_acquire(waited, contended);
+    stats.record_retries(attempt);
+    span.waited(waited);
+    let event = LockEvent::new(state, ident, site)
+        .waited(waited)
+        .contended(contended);
+    observer::notify(|o| o.on_acquired(&event));
+    result.map_err(AcquireError::Poisoned)
 }
 
-#[cfg(feature = "1_46_0")]
-fn print_id(loc: &Location, id: usize) -> String {
-    format!("Lock {} at {}:{}", id, loc.file(), loc.line())
+// Names the lock and where the caller is locking it.
+#[track_caller]
+fn print_id(state: &LockState) -> String {
+    let loc = Location::caller();
+    format!("{}, locked at {}:{}", state.label(), loc.file(), loc.line())
 }
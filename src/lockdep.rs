@@ -0,0 +1,218 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{Mutex as StdMutex, PoisonError as StdPoisonError},
+};
+
+use crate::{
+    observer,
+    state::{LockInfo, LockState},
+    CallSite,
+};
+
+// Where lock orders are recorded: per lock class, so that orders seen on
+// different instances of the same classes are compared, and the graph stays
+// bounded however many locks come and go. Locks of unknown origin are
+// tracked individually, and forgotten when dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Key {
+    Class(&'static str, CallSite),
+    Instance(usize),
+}
+
+fn key(lock: &LockInfo) -> Key {
+    if lock.created.file().is_some() {
+        Key::Class(lock.kind, lock.created)
+    } else {
+        Key::Instance(lock.id)
+    }
+}
+
+/// One observed ordering: `then` was locked at `then_site` while `first`,
+/// locked at `first_site`, was still held.
+#[derive(Clone, Debug)]
+pub struct LockOrder {
+    first: LockInfo,
+    first_site: CallSite,
+    then: LockInfo,
+    then_site: CallSite,
+}
+
+impl LockOrder {
+    pub fn first(&self) -> &LockInfo {
+        &self.first
+    }
+
+    pub fn first_site(&self) -> CallSite {
+        self.first_site
+    }
+
+    pub fn then(&self) -> &LockInfo {
+        &self.then
+    }
+
+    pub fn then_site(&self) -> CallSite {
+        self.then_site
+    }
+}
+
+impl fmt::Display for LockOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, locked at {}, then {}, locked at {}",
+            self.first, self.first_site, self.then, self.then_site
+        )
+    }
+}
+
+/// Two locks taken in the opposite order to one seen before, which can
+/// deadlock if both orders run at once.
+#[derive(Clone, Debug)]
+pub struct LockOrderInversion {
+    order: LockOrder,
+    earlier: Vec<LockOrder>,
+}
+
+impl LockOrderInversion {
+    /// The order just taken.
+    pub fn order(&self) -> &LockOrder {
+        &self.order
+    }
+
+    /// The chain of orders seen before that it inverts.
+    pub fn earlier(&self) -> &[LockOrder] {
+        &self.earlier
+    }
+}
+
+impl fmt::Display for LockOrderInversion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} inverts the earlier order:", self.order)?;
+        for order in &self.earlier {
+            write!(f, "\n    {}", order)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Graph {
+    edges: HashMap<Key, HashMap<Key, LockOrder>>,
+    reported: HashSet<(Key, Key)>,
+}
+
+static GRAPH: StdMutex<Option<Graph>> = StdMutex::new(None);
+
+thread_local! {
+    static HELD: RefCell<Vec<(LockInfo, CallSite)>> = const { RefCell::new(Vec::new()) };
+}
+
+pub(crate) fn acquired(state: &LockState, site: CallSite) {
+    let lock = state.info();
+    // Instances of one class have no order among themselves to check.
+    let held: Vec<(LockInfo, CallSite)> = HELD.with(|held| {
+        held.borrow()
+            .iter()
+            .filter(|(first, _)| key(first) != key(&lock))
+            .cloned()
+            .collect()
+    });
+    if !held.is_empty() {
+        // Observers are told once the graph is unlocked, so they may lock.
+        let inversions: Vec<LockOrderInversion> = {
+            let mut graph = GRAPH.lock().unwrap_or_else(StdPoisonError::into_inner);
+            let graph = graph.get_or_insert_with(Graph::default);
+            held.into_iter()
+                .filter_map(|(first, first_site)| {
+                    graph.add(LockOrder {
+                        first,
+                        first_site,
+                        then: lock.clone(),
+                        then_site: site,
+                    })
+                })
+                .collect()
+        };
+        for inversion in &inversions {
+            observer::notify(|o| o.on_lock_order_inversion(inversion));
+        }
+    }
+    HELD.with(|held| held.borrow_mut().push((lock, site)));
+}
+
+pub(crate) fn released(id: usize) {
+    HELD.with(|held| {
+        let mut held = held.borrow_mut();
+        if let Some(pos) = held.iter().rposition(|(held, _)| held.id == id) {
+            held.remove(pos);
+        }
+    });
+}
+
+// Forgets a dropped lock tracked on its own. Classes outlive their
+// instances, so their orders are kept.
+pub(crate) fn dropped(id: usize) {
+    let key = Key::Instance(id);
+    let mut graph = GRAPH.lock().unwrap_or_else(StdPoisonError::into_inner);
+    if let Some(graph) = graph.as_mut() {
+        graph.edges.remove(&key);
+        for out in graph.edges.values_mut() {
+            out.remove(&key);
+        }
+        graph.edges.retain(|_, out| !out.is_empty());
+        graph
+            .reported
+            .retain(|&(from, to)| from != key && to != key);
+    }
+}
+
+impl Graph {
+    // Records `order`, returning the inversion it makes, if new.
+    fn add(&mut self, order: LockOrder) -> Option<LockOrderInversion> {
+        let (first, then) = (key(&order.first), key(&order.then));
+        if self
+            .edges
+            .get(&first)
+            .map_or(false, |out| out.contains_key(&then))
+        {
+            return None;
+        }
+
+        let inversion = match self.path(then, first) {
+            Some(path) if self.reported.insert((first, then)) => Some(LockOrderInversion {
+                order: order.clone(),
+                earlier: path
+                    .windows(2)
+                    .map(|pair| self.edges[&pair[0]][&pair[1]].clone())
+                    .collect(),
+            }),
+            _ => None,
+        };
+
+        self.edges.entry(first).or_default().insert(then, order);
+        inversion
+    }
+
+    // Depth-first search for an existing chain of orderings `from` -> `to`.
+    fn path(&self, from: Key, to: Key) -> Option<Vec<Key>> {
+        let mut seen = HashSet::new();
+        let mut stack = vec![vec![from]];
+        while let Some(path) = stack.pop() {
+            let last = *path.last().expect("paths are never empty");
+            if last == to {
+                return Some(path);
+            }
+            if !seen.insert(last) {
+                continue;
+            }
+            for &next in self.edges.get(&last).into_iter().flat_map(HashMap::keys) {
+                let mut longer = path.clone();
+                longer.push(next);
+                stack.push(longer);
+            }
+        }
+        None
+    }
+}
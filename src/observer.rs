@@ -0,0 +1,450 @@
+use std::{
+    fmt,
+    sync::{Arc, PoisonError as StdPoisonError, RwLock as StdRwLock},
+    time::Duration,
+};
+
+#[cfg(feature = "backtrace")]
+use std::backtrace::Backtrace;
+
+use log::{debug, error, info, log, trace, warn, Level};
+
+#[cfg(feature = "lockdep")]
+use crate::LockOrderInversion;
+#[cfg(feature = "watchdog")]
+use crate::Stall;
+use crate::{
+    state::LockState, CallSite, CondvarEvent, Deadlock, Holder, PoisonInfo, PoisonPolicy,
+    ThreadInfo, Thresholds,
+};
+
+type Observers = Arc<[Arc<dyn LockObserver>]>;
+
+// `None` until someone installs observers, meaning only `LogObserver`.
+static OBSERVERS: StdRwLock<Option<Observers>> = StdRwLock::new(None);
+
+/// Receives every lock event as it happens. All callbacks default to doing
+/// nothing.
+///
+/// Callbacks run on the thread performing the lock operation, so they should
+/// be quick and must not lock the lock they are told about.
+pub trait LockObserver: Send + Sync {
+    /// The lock was contended and the thread starts waiting for it.
+    fn on_wait_start(&self, _event: &LockEvent<'_>) {}
+
+    /// The thread is still waiting. Called on a schedule rather than after
+    /// every retry: at twice the previous wait each time, and whenever the
+    /// wait crosses one of the lock's wait thresholds. For threads blocked
+    /// inside the underlying lock this is called from a background thread.
+    fn on_still_waiting(&self, _event: &LockEvent<'_>) {}
+
+    fn on_acquired(&self, _event: &LockEvent<'_>) {}
+
+    fn on_released(&self, _event: &LockEvent<'_>) {}
+
+    /// The holder panicked, poisoning the lock.
+    fn on_poisoned(&self, _event: &LockEvent<'_>) {}
+
+    /// A timed acquisition gave up.
+    fn on_timeout(&self, _event: &LockEvent<'_>) {}
+
+    /// A `try_lock` found the lock taken.
+    fn on_try_failed(&self, _event: &LockEvent<'_>) {}
+
+    /// The thread tried to lock a `Mutex` it already holds. The event's
+    /// holder says where it took it first.
+    fn on_reentry(&self, _event: &LockEvent<'_>) {}
+
+    /// The thread acquired a poisoned lock, which `policy` then applies to.
+    fn on_poison_found(&self, _event: &LockEvent<'_>, _policy: PoisonPolicy) {}
+
+    fn on_poison_cleared(&self, _event: &LockEvent<'_>) {}
+
+    /// Threads are waiting on each other in a cycle. Each cycle is reported
+    /// once, by whichever of its waiters finds it first.
+    fn on_deadlock(&self, _deadlock: &Deadlock) {}
+
+    /// Locks were taken in an order that inverts one seen before. Only
+    /// called with the `lockdep` feature.
+    #[cfg(feature = "lockdep")]
+    fn on_lock_order_inversion(&self, _inversion: &LockOrderInversion) {}
+
+    /// The watchdog found a lock stuck. Called on the watchdog thread.
+    #[cfg(feature = "watchdog")]
+    fn on_stall(&self, _stall: &Stall) {}
+
+    /// A wait or hold outlasted the backtrace threshold; `backtrace` is
+    /// where it started.
+    #[cfg(feature = "backtrace")]
+    #[clippy::msrv = "1.65"]
+    fn on_backtrace(&self, _event: &LockEvent<'_>, _backtrace: &Backtrace) {}
+
+    /// A thread starts waiting on a `Condvar`.
+    fn on_condvar_wait(&self, _event: &CondvarEvent<'_>) {}
+
+    /// A thread waiting on a `Condvar` woke up, was notified, timed out or
+    /// woke spuriously, and has the mutex back.
+    fn on_condvar_wake(&self, _event: &CondvarEvent<'_>) {}
+
+    fn on_condvar_notify(&self, _event: &CondvarEvent<'_>) {}
+}
+
+/// One lock operation, as reported to a `LockObserver`.
+#[derive(Clone, Debug)]
+pub struct LockEvent<'a> {
+    pub(crate) id: usize,
+    pub(crate) kind: &'static str,
+    pub(crate) name: Option<&'a str>,
+    pub(crate) created: CallSite,
+    pub(crate) ident: &'a str,
+    pub(crate) site: CallSite,
+    pub(crate) thread: ThreadInfo,
+    pub(crate) waited: Option<Duration>,
+    pub(crate) contended: bool,
+    pub(crate) held: Option<Duration>,
+    pub(crate) holder: Option<Holder>,
+    pub(crate) poisoned_by: Option<PoisonInfo>,
+    pub(crate) wait_thresholds: Option<Thresholds>,
+    pub(crate) hold_thresholds: Option<Thresholds>,
+}
+
+impl<'a> LockEvent<'a> {
+    pub(crate) fn new(state: &'a LockState, ident: &'a str, site: CallSite) -> Self {
+        Self {
+            id: state.id,
+            kind: state.kind,
+            name: state.name.as_deref(),
+            created: state.created,
+            ident,
+            site,
+            thread: ThreadInfo::current(),
+            waited: None,
+            contended: false,
+            held: None,
+            holder: None,
+            poisoned_by: None,
+            wait_thresholds: state.wait_thresholds,
+            hold_thresholds: state.hold_thresholds,
+        }
+    }
+
+    pub(crate) fn waited(self, waited: Duration) -> Self {
+        Self {
+            waited: Some(waited),
+            ..self
+        }
+    }
+
+    pub(crate) fn contended(self, contended: bool) -> Self {
+        Self { contended, ..self }
+    }
+
+    pub(crate) fn held(self, held: Duration) -> Self {
+        Self {
+            held: Some(held),
+            ..self
+        }
+    }
+
+    pub(crate) fn on_thread(self, thread: ThreadInfo) -> Self {
+        Self { thread, ..self }
+    }
+
+    pub(crate) fn holder(self, holder: Option<Holder>) -> Self {
+        Self { holder, ..self }
+    }
+
+    pub(crate) fn poisoned_by(self, poisoned_by: Option<PoisonInfo>) -> Self {
+        Self {
+            poisoned_by,
+            ..self
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// `"Mutex"` or `"RwLock"`.
+    pub fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name
+    }
+
+    /// Where the lock was created, which identifies its lock class.
+    pub fn created(&self) -> CallSite {
+        self.created
+    }
+
+    /// Where the lock operation was requested.
+    pub fn site(&self) -> CallSite {
+        self.site
+    }
+
+    pub fn thread(&self) -> &ThreadInfo {
+        &self.thread
+    }
+
+    /// How long the thread has waited, for every event but releases and
+    /// poisonings.
+    pub fn wait_time(&self) -> Option<Duration> {
+        self.waited
+    }
+
+    /// For acquisitions, whether the lock was taken and the thread had to
+    /// wait for it. Uncontended acquisitions still take a little while.
+    pub fn was_contended(&self) -> bool {
+        self.contended
+    }
+
+    /// How long the lock was held, for releases and poisonings.
+    pub fn hold_time(&self) -> Option<Duration> {
+        self.held
+    }
+
+    /// Who held the lock while this thread waited, if known.
+    pub fn holder_info(&self) -> Option<&Holder> {
+        self.holder.as_ref()
+    }
+
+    /// For poisoned locks, the critical section that poisoned it, if known.
+    pub fn poison_info(&self) -> Option<&PoisonInfo> {
+        self.poisoned_by.as_ref()
+    }
+
+    /// The wait thresholds configured for this lock, if it overrides the
+    /// observer's own.
+    pub fn wait_thresholds(&self) -> Option<&Thresholds> {
+        self.wait_thresholds.as_ref()
+    }
+
+    /// The hold thresholds configured for this lock, if it overrides the
+    /// observer's own.
+    pub fn hold_thresholds(&self) -> Option<&Thresholds> {
+        self.hold_thresholds.as_ref()
+    }
+}
+
+impl fmt::Display for LockEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.ident)
+    }
+}
+
+/// Reports events through the `log` crate, escalating the level the longer
+/// a wait or hold lasts. Installed unless replaced with `set_observers`.
+///
+/// Locks built with their own thresholds use those instead.
+#[derive(Clone, Copy, Debug)]
+pub struct LogObserver {
+    wait: Thresholds,
+    hold: Thresholds,
+}
+
+static DEFAULT_LOG: LogObserver = LogObserver {
+    wait: Thresholds::WAIT,
+    hold: Thresholds::HOLD,
+};
+
+impl Default for LogObserver {
+    fn default() -> Self {
+        DEFAULT_LOG
+    }
+}
+
+impl LogObserver {
+    pub fn wait_thresholds(mut self, thresholds: Thresholds) -> Self {
+        self.wait = thresholds;
+        self
+    }
+
+    pub fn hold_thresholds(mut self, thresholds: Thresholds) -> Self {
+        self.hold = thresholds;
+        self
+    }
+}
+
+impl LockObserver for LogObserver {
+    fn on_still_waiting(&self, event: &LockEvent<'_>) {
+        let waited = event.waited.unwrap_or_default();
+        let thresholds = event.wait_thresholds.unwrap_or(self.wait);
+        if let Some(level) = thresholds.level(waited) {
+            let held_by = match &event.holder {
+                Some(holder) => format!(", {}", holder),
+                None => String::new(),
+            };
+            log!(level, "{} - Waiting {:?}{}", event, waited, held_by);
+        }
+    }
+
+    fn on_acquired(&self, event: &LockEvent<'_>) {
+        trace!("{} - Locked", event);
+    }
+
+    // Escalates like waiting, but for how long the lock was held: a slow
+    // critical section is as much of a problem as a slow waiter.
+    fn on_released(&self, event: &LockEvent<'_>) {
+        let held = event.held.unwrap_or_default();
+        let thresholds = event.hold_thresholds.unwrap_or(self.hold);
+        let level = thresholds.level(held).unwrap_or(Level::Trace);
+        log!(level, "{} - Released after {:?}", event, held);
+    }
+
+    fn on_poisoned(&self, event: &LockEvent<'_>) {
+        warn!(
+            "{} - Poisoned by thread {} after {:?}",
+            event,
+            event.thread,
+            event.held.unwrap_or_default()
+        );
+    }
+
+    fn on_timeout(&self, event: &LockEvent<'_>) {
+        info!(
+            "{} - Timed out after {:?}",
+            event,
+            event.waited.unwrap_or_default()
+        );
+    }
+
+    fn on_try_failed(&self, event: &LockEvent<'_>) {
+        match &event.holder {
+            Some(holder) => trace!("{} - Try lock failed, {}", event, holder),
+            None => trace!("{} - Try lock failed", event),
+        }
+    }
+
+    fn on_reentry(&self, event: &LockEvent<'_>) {
+        let held_at = event.holder.as_ref().map(|holder| holder.site);
+        error!(
+            "{} - Self-deadlock, thread {} already holds it since {}",
+            event,
+            event.thread,
+            held_at.unwrap_or_default()
+        );
+    }
+
+    fn on_poison_found(&self, event: &LockEvent<'_>, policy: PoisonPolicy) {
+        match (policy, &event.poisoned_by) {
+            (PoisonPolicy::Ignore, _) => debug!("{} - Ignoring poison", event),
+            (_, Some(info)) => debug!("{} - Lock is {}", event, info),
+            (_, None) => debug!("{} - Lock is poisoned", event),
+        }
+    }
+
+    fn on_poison_cleared(&self, event: &LockEvent<'_>) {
+        debug!("{} - Poison cleared", event);
+    }
+
+    fn on_deadlock(&self, deadlock: &Deadlock) {
+        error!("{}", deadlock);
+    }
+
+    #[cfg(feature = "lockdep")]
+    fn on_lock_order_inversion(&self, inversion: &LockOrderInversion) {
+        warn!("Potential deadlock: {}", inversion);
+    }
+
+    #[cfg(feature = "watchdog")]
+    fn on_stall(&self, stall: &Stall) {
+        warn!("{}", stall);
+    }
+
+    #[cfg(feature = "backtrace")]
+    #[clippy::msrv = "1.65"]
+    fn on_backtrace(&self, event: &LockEvent<'_>, backtrace: &Backtrace) {
+        match event.held {
+            Some(held) => warn!("{} - Held {:?} at:\n{}", event, held, backtrace),
+            None => warn!(
+                "{} - Waiting {:?} at:\n{}",
+                event,
+                event.waited.unwrap_or_default(),
+                backtrace
+            ),
+        }
+    }
+
+    fn on_condvar_wait(&self, event: &CondvarEvent<'_>) {
+        let mutex = event.mutex.unwrap_or_default();
+        match event.timeout {
+            Some(timeout) => trace!(
+                "{} - Condvar {} waiting at {} for up to {:?}",
+                mutex,
+                event.id,
+                event.site,
+                timeout
+            ),
+            None => trace!("{} - Condvar {} waiting at {}", mutex, event.id, event.site),
+        }
+    }
+
+    fn on_condvar_wake(&self, event: &CondvarEvent<'_>) {
+        let mutex = event.mutex.unwrap_or_default();
+        let waited = event.waited.unwrap_or_default();
+        match event.notified_at {
+            _ if event.timed_out => {
+                trace!(
+                    "{} - Condvar {} timed out after {:?}",
+                    mutex,
+                    event.id,
+                    waited
+                )
+            }
+            Some(notified_at) => trace!(
+                "{} - Condvar {} woken after {:?} by notify at {}, reacquired in {:?}",
+                mutex,
+                event.id,
+                waited,
+                notified_at,
+                event.reacquired.unwrap_or_default()
+            ),
+            None => trace!(
+                "{} - Condvar {} woken spuriously after {:?}",
+                mutex,
+                event.id,
+                waited
+            ),
+        }
+    }
+
+    fn on_condvar_notify(&self, event: &CondvarEvent<'_>) {
+        let which = if event.notify_all {
+            "notify_all"
+        } else {
+            "notify_one"
+        };
+        trace!("Condvar {} - {} at {}", event.id, which, event.site);
+    }
+}
+
+/// Replaces every installed observer, including the default `LogObserver`.
+pub fn set_observers(observers: Vec<Arc<dyn LockObserver>>) {
+    *OBSERVERS.write().unwrap_or_else(StdPoisonError::into_inner) = Some(observers.into());
+}
+
+/// Installs `observer` next to the ones already in place.
+pub fn add_observer(observer: Arc<dyn LockObserver>) {
+    let mut observers = OBSERVERS.write().unwrap_or_else(StdPoisonError::into_inner);
+    let mut list = match observers.as_deref() {
+        Some(list) => list.to_vec(),
+        None => vec![Arc::new(LogObserver::default()) as Arc<dyn LockObserver>],
+    };
+    list.push(observer);
+    *observers = Some(list.into());
+}
+
+// Calls `f` on every observer. The list is cloned out first so observers may
+// install others without deadlocking.
+pub(crate) fn notify(f: impl Fn(&dyn LockObserver)) {
+    let observers = OBSERVERS
+        .read()
+        .unwrap_or_else(StdPoisonError::into_inner)
+        .clone();
+    match observers {
+        Some(observers) => observers.iter().for_each(|observer| f(&**observer)),
+        None => f(&DEFAULT_LOG),
+    }
+}
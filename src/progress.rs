@@ -0,0 +1,161 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex as StdMutex, MutexGuard as StdMutexGuard, PoisonError as StdPoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    deadlock,
+    observer::{self, LockEvent},
+    state::LockState,
+    CallSite, ThreadInfo, Thresholds, DEFAULT_SPIN,
+};
+
+// How often the reporter looks at threads blocked in a lock.
+const TICK: Duration = Duration::from_millis(10);
+
+static BLOCKED: StdMutex<Blocked> = StdMutex::new(Blocked {
+    waiters: None,
+    next: 0,
+    running: false,
+});
+
+// When a waiter is next due to report that it is still waiting: twice as
+// long into the wait each time, so a long wait is not a flood of reports,
+// but never later than the next threshold, so escalations are on time.
+#[derive(Debug)]
+pub(crate) struct ReportSchedule {
+    next: Duration,
+    thresholds: Thresholds,
+}
+
+impl ReportSchedule {
+    pub(crate) fn new(state: &LockState) -> Self {
+        let thresholds = state.wait_thresholds.unwrap_or(Thresholds::WAIT);
+        let first = Duration::from_micros(DEFAULT_SPIN as u64);
+        Self {
+            next: thresholds.next_after(Duration::from_secs(0)).min(first),
+            thresholds,
+        }
+    }
+
+    pub(crate) fn due(&mut self, waited: Duration) -> bool {
+        if waited < self.next {
+            return false;
+        }
+        self.next = self.thresholds.next_after(waited).min(waited * 2);
+        true
+    }
+
+    // How much longer a waiter may sleep without missing its next report.
+    pub(crate) fn until_due(&self, waited: Duration) -> Duration {
+        self.next.saturating_sub(waited)
+    }
+}
+
+// Threads waiting in the underlying blocking lock, which cannot report on
+// themselves. A reporter thread does it for them while there are any.
+struct Blocked {
+    waiters: Option<HashMap<u64, Waiter>>,
+    next: u64,
+    running: bool,
+}
+
+struct Waiter {
+    state: Arc<LockState>,
+    ident: String,
+    site: CallSite,
+    start: Instant,
+    thread: ThreadInfo,
+    schedule: ReportSchedule,
+}
+
+// Stops the reports for a blocked thread when dropped.
+pub(crate) struct Reporting(u64);
+
+impl Drop for Reporting {
+    fn drop(&mut self) {
+        if let Some(waiters) = blocked().waiters.as_mut() {
+            waiters.remove(&self.0);
+        }
+    }
+}
+
+pub(crate) fn start(
+    state: &Arc<LockState>,
+    ident: &str,
+    site: CallSite,
+    start: Instant,
+) -> Reporting {
+    let mut blocked = blocked();
+    let key = blocked.next;
+    blocked.next += 1;
+    blocked.waiters.get_or_insert_with(HashMap::new).insert(
+        key,
+        Waiter {
+            state: Arc::clone(state),
+            ident: ident.to_owned(),
+            site,
+            start,
+            thread: ThreadInfo::current(),
+            schedule: ReportSchedule::new(state),
+        },
+    );
+    if !blocked.running {
+        blocked.running = true;
+        thread::Builder::new()
+            .name("trace-mutex-progress".into())
+            .spawn(run)
+            .expect("failed to spawn the progress reporter thread");
+    }
+    Reporting(key)
+}
+
+fn run() {
+    loop {
+        thread::sleep(TICK);
+        let due: Vec<_> = {
+            let mut blocked = blocked();
+            let waiters = match blocked.waiters.as_mut() {
+                Some(waiters) if !waiters.is_empty() => waiters,
+                _ => {
+                    blocked.running = false;
+                    return;
+                }
+            };
+            waiters
+                .values_mut()
+                .filter_map(|waiter| {
+                    let waited = waiter.start.elapsed();
+                    if !waiter.schedule.due(waited) {
+                        return None;
+                    }
+                    Some((
+                        Arc::clone(&waiter.state),
+                        waiter.ident.clone(),
+                        waiter.site,
+                        waiter.thread.clone(),
+                        waited,
+                    ))
+                })
+                .collect()
+        };
+
+        for (state, ident, site, thread, waited) in due {
+            let id = thread.id();
+            let event = LockEvent::new(&state, &ident, site)
+                .on_thread(thread)
+                .waited(waited)
+                .holder(state.holder.get());
+            observer::notify(|o| o.on_still_waiting(&event));
+            if waited >= state.deadlock_check_after() {
+                deadlock::check_blocked(id);
+            }
+        }
+    }
+}
+
+fn blocked() -> StdMutexGuard<'static, Blocked> {
+    BLOCKED.lock().unwrap_or_else(StdPoisonError::into_inner)
+}
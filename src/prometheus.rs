@@ -0,0 +1,245 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    sync::{Mutex as StdMutex, MutexGuard as StdMutexGuard, PoisonError as StdPoisonError},
+    time::Duration,
+};
+
+use crate::{
+    observer::{LockEvent, LockObserver},
+    stats::micros,
+    CallSite,
+};
+
+// Upper bounds of the histogram buckets, in microseconds.
+const BUCKETS_US: [u64; 8] = [
+    10,
+    100,
+    1_000,
+    10_000,
+    100_000,
+    1_000_000,
+    10_000_000,
+    u64::MAX,
+];
+
+/// Collects per-lock, per-call-site counters and wait and hold histograms,
+/// and renders them in the Prometheus text exposition format.
+///
+/// The `mutex` label is the lock's name or, for unnamed locks, where it was
+/// created, so every instance of a lock class shares one series and short
+/// lived locks do not add series of their own. Unnamed locks created at an
+/// unknown site get a series each, labelled like "Mutex #3".
+///
+/// Install it with `add_observer` and serve `render()` from a metrics
+/// endpoint.
+#[derive(Debug, Default)]
+pub struct PrometheusObserver {
+    sites: StdMutex<HashMap<(Series, CallSite), SiteMetrics>>,
+}
+
+// Which locks a series covers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Series {
+    Named(String),
+    Class(CallSite),
+    Instance(&'static str, usize),
+}
+
+impl Series {
+    fn of(event: &LockEvent<'_>) -> Self {
+        match event.name {
+            Some(name) => Series::Named(name.to_owned()),
+            None if event.created.file().is_some() => Series::Class(event.created),
+            None => Series::Instance(event.kind, event.id),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct SiteMetrics {
+    acquisitions: u64,
+    contentions: u64,
+    wait: Histogram,
+    hold: Histogram,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Histogram {
+    buckets: [u64; BUCKETS_US.len()],
+    sum_us: u64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, d: Duration) {
+        let us = micros(d);
+        let bucket = BUCKETS_US
+            .iter()
+            .position(|&bound| us <= bound)
+            .unwrap_or(BUCKETS_US.len() - 1);
+        self.buckets[bucket] += 1;
+        self.sum_us = self.sum_us.saturating_add(us);
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        for (bucket, n) in self.buckets.iter_mut().zip(&other.buckets) {
+            *bucket += n;
+        }
+        self.sum_us = self.sum_us.saturating_add(other.sum_us);
+        self.count += other.count;
+    }
+}
+
+impl PrometheusObserver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Renders every metric collected so far.
+    pub fn render(&self) -> String {
+        // Sites differing only in column share their text, and a series.
+        let mut series: BTreeMap<(String, String), SiteMetrics> = BTreeMap::new();
+        for ((lock, site), metrics) in self.sites().iter() {
+            let mutex = match lock {
+                Series::Named(name) => name.clone(),
+                Series::Class(created) => created.to_string(),
+                Series::Instance(kind, id) => format!("{} #{}", kind, id),
+            };
+            let merged = series.entry((mutex, site.to_string())).or_default();
+            merged.acquisitions += metrics.acquisitions;
+            merged.contentions += metrics.contentions;
+            merged.wait.merge(&metrics.wait);
+            merged.hold.merge(&metrics.hold);
+        }
+
+        let mut out = String::new();
+        header(
+            &mut out,
+            "trace_mutex_acquisitions_total",
+            "counter",
+            "Lock acquisitions.",
+        );
+        for ((mutex, site), metrics) in &series {
+            let _ = writeln!(
+                out,
+                "trace_mutex_acquisitions_total{{{}}} {}",
+                labels(mutex, site),
+                metrics.acquisitions
+            );
+        }
+        header(
+            &mut out,
+            "trace_mutex_contentions_total",
+            "counter",
+            "Acquisitions that found the lock taken and had to wait.",
+        );
+        for ((mutex, site), metrics) in &series {
+            let _ = writeln!(
+                out,
+                "trace_mutex_contentions_total{{{}}} {}",
+                labels(mutex, site),
+                metrics.contentions
+            );
+        }
+        header(
+            &mut out,
+            "trace_mutex_wait_seconds",
+            "histogram",
+            "Time spent waiting to acquire a lock.",
+        );
+        for ((mutex, site), metrics) in &series {
+            histogram(
+                &mut out,
+                "trace_mutex_wait_seconds",
+                &labels(mutex, site),
+                &metrics.wait,
+            );
+        }
+        header(
+            &mut out,
+            "trace_mutex_hold_seconds",
+            "histogram",
+            "Time a lock was held before being released.",
+        );
+        for ((mutex, site), metrics) in &series {
+            histogram(
+                &mut out,
+                "trace_mutex_hold_seconds",
+                &labels(mutex, site),
+                &metrics.hold,
+            );
+        }
+        out
+    }
+
+    fn update(&self, event: &LockEvent<'_>, f: impl FnOnce(&mut SiteMetrics)) {
+        let mut sites = self.sites();
+        f(sites.entry((Series::of(event), event.site)).or_default());
+    }
+
+    fn sites(&self) -> StdMutexGuard<'_, HashMap<(Series, CallSite), SiteMetrics>> {
+        self.sites.lock().unwrap_or_else(StdPoisonError::into_inner)
+    }
+}
+
+impl LockObserver for PrometheusObserver {
+    fn on_wait_start(&self, event: &LockEvent<'_>) {
+        self.update(event, |metrics| metrics.contentions += 1);
+    }
+
+    fn on_acquired(&self, event: &LockEvent<'_>) {
+        self.update(event, |metrics| {
+            metrics.acquisitions += 1;
+            metrics.wait.observe(event.waited.unwrap_or_default());
+        });
+    }
+
+    fn on_released(&self, event: &LockEvent<'_>) {
+        self.update(event, |metrics| {
+            metrics.hold.observe(event.held.unwrap_or_default())
+        });
+    }
+}
+
+fn header(out: &mut String, metric: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", metric, help);
+    let _ = writeln!(out, "# TYPE {} {}", metric, kind);
+}
+
+fn histogram(out: &mut String, metric: &str, labels: &str, histogram: &Histogram) {
+    let mut cumulative = 0;
+    for (&bound, &n) in BUCKETS_US.iter().zip(&histogram.buckets) {
+        cumulative += n;
+        let le = if bound == u64::MAX {
+            "+Inf".to_owned()
+        } else {
+            (bound as f64 / 1e6).to_string()
+        };
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{},le=\"{}\"}} {}",
+            metric, labels, le, cumulative
+        );
+    }
+    let _ = writeln!(
+        out,
+        "{}_sum{{{}}} {}",
+        metric,
+        labels,
+        histogram.sum_us as f64 / 1e6
+    );
+    let _ = writeln!(out, "{}_count{{{}}} {}", metric, labels, histogram.count);
+}
+
+fn labels(mutex: &str, site: &str) -> String {
+    format!("mutex=\"{}\",site=\"{}\"", escape(mutex), escape(site))
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
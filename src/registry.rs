@@ -0,0 +1,185 @@
+//! Every live lock, for inspecting lock state from admin endpoints or at
+//! shutdown. Only built with the `registry` feature.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::{Arc, Mutex as StdMutex, PoisonError as StdPoisonError, Weak},
+};
+
+use crate::{
+    deadlock,
+    state::{Label, LockState},
+    CallSite, Holder, LockStats, Waiter,
+};
+
+type Classes = HashMap<(&'static str, CallSite), LockClass>;
+
+static LOCKS: StdMutex<Option<BTreeMap<usize, Weak<LockState>>>> = StdMutex::new(None);
+// What dropped locks leave behind, per class.
+static DROPPED: StdMutex<Option<Classes>> = StdMutex::new(None);
+
+/// The state of one live lock at the time of the snapshot.
+#[derive(Clone, Debug)]
+pub struct LockSnapshot {
+    pub id: usize,
+    pub kind: &'static str,
+    pub name: Option<String>,
+    pub created: CallSite,
+    pub holder: Option<Holder>,
+    pub waiters: Vec<Waiter>,
+    pub stats: LockStats,
+}
+
+impl LockSnapshot {
+    pub(crate) fn label(&self) -> Label<'_> {
+        Label {
+            kind: self.kind,
+            id: self.id,
+            name: self.name.as_deref(),
+            created: self.created,
+        }
+    }
+}
+
+impl fmt::Display for LockSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.label())?;
+        match &self.holder {
+            Some(holder) => write!(f, ": {}", holder)?,
+            None => f.write_str(": unlocked")?,
+        }
+        for waiter in &self.waiters {
+            write!(f, "\n    {}", waiter)?;
+        }
+        write_stats(f, &self.stats)
+    }
+}
+
+/// Every lock created at one site, taken together. Instances come and go,
+/// but usually share a purpose and a locking discipline.
+#[derive(Clone, Debug)]
+pub struct LockClass {
+    pub kind: &'static str,
+    pub created: CallSite,
+    pub live: usize,
+    pub dropped: usize,
+    /// The merged stats of every instance, live or dropped.
+    pub stats: LockStats,
+}
+
+impl LockClass {
+    fn new(kind: &'static str, created: CallSite) -> Self {
+        Self {
+            kind,
+            created,
+            live: 0,
+            dropped: 0,
+            stats: LockStats {
+                created,
+                ..LockStats::default()
+            },
+        }
+    }
+}
+
+impl fmt::Display for LockClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} created at {}: {} live, {} dropped",
+            self.kind, self.created, self.live, self.dropped
+        )?;
+        write_stats(f, &self.stats)
+    }
+}
+
+fn write_stats(f: &mut fmt::Formatter<'_>, stats: &LockStats) -> fmt::Result {
+    write!(
+        f,
+        "\n    {} acquisitions ({} contended, {} timed out, {} poisoned), wait {:?} total / {:?} max, hold {:?} total / {:?} max",
+        stats.acquisitions,
+        stats.contended,
+        stats.timeouts,
+        stats.poisonings,
+        stats.total_wait,
+        stats.max_wait,
+        stats.total_hold,
+        stats.max_hold
+    )
+}
+
+pub(crate) fn join(state: &Arc<LockState>) {
+    locks()
+        .get_or_insert_with(BTreeMap::new)
+        .insert(state.id, Arc::downgrade(state));
+}
+
+pub(crate) fn leave(state: &LockState) {
+    if let Some(locks) = locks().as_mut() {
+        locks.remove(&state.id);
+    }
+    let mut dropped = DROPPED.lock().unwrap_or_else(StdPoisonError::into_inner);
+    let class = dropped
+        .get_or_insert_with(HashMap::new)
+        .entry((state.kind, state.created))
+        .or_insert_with(|| LockClass::new(state.kind, state.created));
+    class.dropped += 1;
+    class.stats.merge(&state.stats());
+}
+
+/// Lists every live lock, ordered by id.
+pub fn snapshot() -> Vec<LockSnapshot> {
+    // Upgrade under the registry lock but inspect outside it: dropping the
+    // last handle to a lock calls `leave`, which needs the registry lock.
+    let live: Vec<Arc<LockState>> = locks()
+        .iter()
+        .flat_map(BTreeMap::values)
+        .filter_map(Weak::upgrade)
+        .collect();
+    live.iter()
+        .map(|state| LockSnapshot {
+            id: state.id,
+            kind: state.kind,
+            name: state.name.as_deref().map(str::to_owned),
+            created: state.created,
+            holder: state.holder.get(),
+            waiters: deadlock::waiters_of(state.id),
+            stats: state.stats(),
+        })
+        .collect()
+}
+
+/// Groups every lock created so far by creation site, ordered by site.
+pub fn classes() -> Vec<LockClass> {
+    let mut classes = DROPPED
+        .lock()
+        .unwrap_or_else(StdPoisonError::into_inner)
+        .clone()
+        .unwrap_or_default();
+    for lock in snapshot() {
+        let class = classes
+            .entry((lock.kind, lock.created))
+            .or_insert_with(|| LockClass::new(lock.kind, lock.created));
+        class.live += 1;
+        class.stats.merge(&lock.stats);
+    }
+    let mut classes: Vec<LockClass> = classes.into_values().collect();
+    classes.sort_by_key(|class| (class.created.file(), class.created.line(), class.kind));
+    classes
+}
+
+/// Renders `snapshot()` for humans, one lock per paragraph.
+pub fn dump() -> String {
+    let locks = snapshot();
+    let mut out = format!("{} live locks", locks.len());
+    for lock in locks {
+        out.push('\n');
+        out.push_str(&lock.to_string());
+    }
+    out
+}
+
+fn locks() -> std::sync::MutexGuard<'static, Option<BTreeMap<usize, Weak<LockState>>>> {
+    LOCKS.lock().unwrap_or_else(StdPoisonError::into_inner)
+}
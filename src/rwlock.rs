@@ -0,0 +1,267 @@
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::{
+        Arc, RwLock as StdRwLock, RwLockReadGuard as StdRwLockReadGuard,
+        RwLockWriteGuard as StdRwLockWriteGuard,
+    },
+    thread,
+    time::Instant,
+};
+
+use crate::{
+    acquire,
+    backtrace::Trace,
+    observer::{self, LockEvent},
+    print_id,
+    span::LockSpan,
+    state::{LockOptions, LockState},
+    AcquireError, CallSite, LockResult, LockStats, PoisonError, WaitStrategy,
+};
+
+#[derive(Debug)]
+pub struct RwLock<T> {
+    inner: StdRwLock<T>,
+    state: Arc<LockState>,
+}
+
+pub struct RwLockReadGuard<'a, T> {
+    inner: StdRwLockReadGuard<'a, T>,
+    state: &'a LockState,
+    id: String,
+    site: CallSite,
+    acquired: Instant,
+    trace: Trace,
+    span: LockSpan,
+}
+
+pub struct RwLockWriteGuard<'a, T> {
+    inner: StdRwLockWriteGuard<'a, T>,
+    state: &'a LockState,
+    id: String,
+    site: CallSite,
+    acquired: Instant,
+    // As for `MutexGuard`: only a panic that starts while the lock is
+    // written poisons it. Readers never do.
+    panicking: bool,
+    trace: Trace,
+    span: LockSpan,
+}
+
+impl<'a, T> RwLockReadGuard<'a, T> {
+    fn new(
+        state: &'a LockState,
+        inner: StdRwLockReadGuard<'a, T>,
+        id: String,
+        site: CallSite,
+    ) -> Self {
+        Self {
+            inner,
+            state,
+            id,
+            site,
+            acquired: Instant::now(),
+            trace: Trace::capture(),
+            span: LockSpan::holding(state, site),
+        }
+    }
+}
+
+impl<'a, T> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        self.inner.deref()
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for RwLockReadGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, T> Drop for RwLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        let held = self.acquired.elapsed();
+        self.state.stats.record_release(held);
+        let event = LockEvent::new(self.state, &self.id, self.site).held(held);
+        observer::notify(|o| o.on_released(&event));
+        self.trace.report(&event);
+        self.span.held(held);
+    }
+}
+
+impl<'a, T> RwLockWriteGuard<'a, T> {
+    fn new(
+        state: &'a LockState,
+        inner: StdRwLockWriteGuard<'a, T>,
+        id: String,
+        site: CallSite,
+    ) -> Self {
+        Self {
+            inner,
+            state,
+            id,
+            site,
+            acquired: Instant::now(),
+            panicking: thread::panicking(),
+            trace: Trace::capture(),
+            span: LockSpan::holding(state, site),
+        }
+    }
+}
+
+impl<'a, T> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        self.inner.deref()
+    }
+}
+
+impl<'a, T> DerefMut for RwLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.deref_mut()
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for RwLockWriteGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, T> Drop for RwLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        let held = self.acquired.elapsed();
+        if !self.panicking && thread::panicking() {
+            self.state.stats.record_poison();
+            let event = LockEvent::new(self.state, &self.id, self.site).held(held);
+            observer::notify(|o| o.on_poisoned(&event));
+        }
+        self.state.stats.record_release(held);
+        let event = LockEvent::new(self.state, &self.id, self.site).held(held);
+        observer::notify(|o| o.on_released(&event));
+        self.trace.report(&event);
+        self.span.held(held);
+    }
+}
+
+impl<T> RwLock<T> {
+    #[track_caller]
+    pub fn new(data: T) -> Self {
+        Self::with_state(data, LockState::new("RwLock", LockOptions::default()))
+    }
+
+    #[track_caller]
+    pub fn named(name: impl Into<String>, data: T) -> Self {
+        let options = LockOptions {
+            name: Some(name.into()),
+            ..LockOptions::default()
+        };
+        Self::with_state(data, LockState::new("RwLock", options))
+    }
+
+    fn with_state(data: T, state: Arc<LockState>) -> Self {
+        Self {
+            inner: StdRwLock::new(data),
+            state,
+        }
+    }
+
+    #[track_caller]
+    pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        let start = Instant::now();
+        let site = CallSite::caller();
+        let ident = format!("{} (read)", print_id(&self.state));
+
+        match acquire(
+            &self.state,
+            &ident,
+            site,
+            start,
+            None,
+            || self.inner.try_read(),
+            || self.inner.read(),
+        ) {
+            Ok(guard) => Ok(RwLockReadGuard::new(&self.state, guard, ident, site)),
+            Err(AcquireError::Poisoned(p)) => Err(PoisonError::new(RwLockReadGuard::new(
+                &self.state,
+                p.into_inner(),
+                ident,
+                site,
+            ))),
+            Err(AcquireError::Reentrant(_)) => unreachable!("RwLock does not track its holder"),
+            Err(AcquireError::TimedOut) => unreachable!("lock without a deadline gave up"),
+        }
+    }
+
+    #[track_caller]
+    pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+        let start = Instant::now();
+        let site = CallSite::caller();
+        let ident = format!("{} (write)", print_id(&self.state));
+
+        match acquire(
+            &self.state,
+            &ident,
+            site,
+            start,
+            None,
+            || self.inner.try_write(),
+            || self.inner.write(),
+        ) {
+            Ok(guard) => Ok(RwLockWriteGuard::new(&self.state, guard, ident, site)),
+            Err(AcquireError::Poisoned(p)) => Err(PoisonError::new(RwLockWriteGuard::new(
+                &self.state,
+                p.into_inner(),
+                ident,
+                site,
+            ))),
+            Err(AcquireError::Reentrant(_)) => unreachable!("RwLock does not track its holder"),
+            Err(AcquireError::TimedOut) => unreachable!("lock without a deadline gave up"),
+        }
+    }
+
+    pub fn wait_strategy(&self) -> Arc<dyn WaitStrategy> {
+        self.state.strategy()
+    }
+
+    pub fn set_wait_strategy(&self, strategy: impl WaitStrategy + 'static) {
+        self.state.set_strategy(Arc::new(strategy));
+    }
+
+    /// Where the lock was created, which identifies its lock class.
+    pub fn created(&self) -> CallSite {
+        self.state.created
+    }
+
+    pub fn stats(&self) -> LockStats {
+        self.state.stats()
+    }
+
+    pub fn reset_stats(&self) {
+        self.state.stats.reset();
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.inner.is_poisoned()
+    }
+
+    #[cfg(feature = "1_77_0")]
+    #[clippy::msrv = "1.77"]
+    pub fn clear_poison(&self) {
+        self.inner.clear_poison();
+    }
+
+    pub fn into_inner(self) -> LockResult<T> {
+        self.inner
+            .into_inner()
+            .map_err(|p| PoisonError::new(p.into_inner()))
+    }
+
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        self.inner
+            .get_mut()
+            .map_err(|p| PoisonError::new(p.into_inner()))
+    }
+}
@@ -0,0 +1,34 @@
+use std::{fmt, panic::Location};
+
+/// Where in the source a lock operation was requested. The default site is
+/// unknown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CallSite {
+    loc: Option<&'static Location<'static>>,
+}
+
+impl CallSite {
+    #[track_caller]
+    pub(crate) fn caller() -> Self {
+        Self {
+            loc: Some(Location::caller()),
+        }
+    }
+
+    pub fn file(&self) -> Option<&'static str> {
+        self.loc.map(Location::file)
+    }
+
+    pub fn line(&self) -> Option<u32> {
+        self.loc.map(Location::line)
+    }
+}
+
+impl fmt::Display for CallSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.file(), self.line()) {
+            (Some(file), Some(line)) => write!(f, "{}:{}", file, line),
+            _ => f.write_str("<unknown>"),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+#[cfg(not(feature = "tracing"))]
+use std::marker::PhantomData;
+use std::time::Duration;
+
+#[cfg(feature = "tracing")]
+use tracing::{field, trace_span, Span};
+
+#[cfg(feature = "tracing")]
+use crate::stats::micros;
+use crate::{state::LockState, CallSite};
+
+// A `tracing` span covering the wait or the hold phase of one acquisition.
+// Without the `tracing` feature this is empty and every method is a no-op.
+// Boxed, and only kept while a subscriber is interested, since one lives in
+// every guard.
+#[derive(Debug)]
+pub(crate) struct LockSpan {
+    #[cfg(feature = "tracing")]
+    span: Option<Box<Span>>,
+}
+
+pub(crate) struct Entered<'a> {
+    #[cfg(feature = "tracing")]
+    _entered: Option<tracing::span::Entered<'a>>,
+    #[cfg(not(feature = "tracing"))]
+    _span: PhantomData<&'a LockSpan>,
+}
+
+impl LockSpan {
+    #[cfg(feature = "tracing")]
+    pub(crate) fn waiting(state: &LockState, site: CallSite) -> Self {
+        Self::new(trace_span!(
+            "lock_wait",
+            mutex_id = state.id,
+            name = state.name.as_deref(),
+            created = %state.created,
+            file = site.file(),
+            line = site.line(),
+            wait_us = field::Empty,
+        ))
+    }
+
+    #[cfg(feature = "tracing")]
+    pub(crate) fn holding(state: &LockState, site: CallSite) -> Self {
+        Self::new(trace_span!(
+            "lock_hold",
+            mutex_id = state.id,
+            name = state.name.as_deref(),
+            created = %state.created,
+            file = site.file(),
+            line = site.line(),
+            hold_us = field::Empty,
+        ))
+    }
+
+    #[cfg(feature = "tracing")]
+    fn new(span: Span) -> Self {
+        Self {
+            span: if span.is_disabled() {
+                None
+            } else {
+                Some(Box::new(span))
+            },
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    pub(crate) fn enter(&self) -> Entered<'_> {
+        Entered {
+            _entered: self.span.as_ref().map(|span| span.enter()),
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    pub(crate) fn waited(&self, waited: Duration) {
+        if let Some(span) = &self.span {
+            let wait_us = micros(waited);
+            span.record("wait_us", wait_us);
+            tracing::trace!(parent: &**span, wait_us, "locked");
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    pub(crate) fn held(&self, held: Duration) {
+        if let Some(span) = &self.span {
+            let hold_us = micros(held);
+            span.record("hold_us", hold_us);
+            tracing::trace!(parent: &**span, hold_us, "released");
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn waiting(_state: &LockState, _site: CallSite) -> Self {
+        Self {}
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn holding(_state: &LockState, _site: CallSite) -> Self {
+        Self {}
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn enter(&self) -> Entered<'_> {
+        Entered { _span: PhantomData }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn waited(&self, _waited: Duration) {}
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn held(&self, _held: Duration) {}
+}
@@ -0,0 +1,197 @@
+use std::{
+    fmt,
+    sync::{atomic::Ordering, Arc, PoisonError as StdPoisonError, RwLock as StdRwLock},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    holder::HolderSlot, stats::Stats, CallSite, ExponentialBackoff, LockStats, Thresholds,
+    WaitStrategy, MUTEX_ID,
+};
+
+// Bookkeeping shared by every lock type, independent of the data it guards.
+#[derive(Debug)]
+pub(crate) struct LockState {
+    pub(crate) id: usize,
+    pub(crate) kind: &'static str,
+    pub(crate) name: Option<Arc<str>>,
+    pub(crate) created: CallSite,
+    pub(crate) wait_thresholds: Option<Thresholds>,
+    pub(crate) hold_thresholds: Option<Thresholds>,
+    pub(crate) stats: Stats,
+    pub(crate) holder: Arc<HolderSlot>,
+    pub(crate) strategy: StdRwLock<Arc<dyn WaitStrategy>>,
+}
+
+// What a lock can be configured with; `None` means the crate default.
+#[derive(Default)]
+pub(crate) struct LockOptions {
+    pub(crate) name: Option<String>,
+    pub(crate) wait_thresholds: Option<Thresholds>,
+    pub(crate) hold_thresholds: Option<Thresholds>,
+    pub(crate) strategy: Option<Arc<dyn WaitStrategy>>,
+}
+
+impl LockState {
+    #[track_caller]
+    pub(crate) fn new(kind: &'static str, options: LockOptions) -> Arc<Self> {
+        let LockOptions {
+            name,
+            wait_thresholds,
+            hold_thresholds,
+            strategy,
+        } = options;
+        let state = Arc::new(Self {
+            id: MUTEX_ID.fetch_add(1, Ordering::AcqRel),
+            kind,
+            name: name.map(Arc::from),
+            created: CallSite::caller(),
+            wait_thresholds,
+            hold_thresholds,
+            stats: Stats::default(),
+            holder: Arc::default(),
+            strategy: StdRwLock::new(
+                strategy.unwrap_or_else(|| Arc::new(ExponentialBackoff::default())),
+            ),
+        });
+        #[cfg(feature = "registry")]
+        crate::registry::join(&state);
+        state
+    }
+
+    // Called by guards that track a single owner, once the lock is theirs.
+    pub(crate) fn held(&self, site: CallSite, since: Instant) {
+        self.holder.set(site, since);
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::acquired(self, site);
+    }
+
+    pub(crate) fn label(&self) -> Label<'_> {
+        Label {
+            kind: self.kind,
+            id: self.id,
+            name: self.name.as_deref(),
+            created: self.created,
+        }
+    }
+
+    pub(crate) fn info(&self) -> LockInfo {
+        LockInfo {
+            id: self.id,
+            kind: self.kind,
+            name: self.name.clone(),
+            created: self.created,
+        }
+    }
+
+    pub(crate) fn stats(&self) -> LockStats {
+        LockStats {
+            created: self.created,
+            ..self.stats.snapshot()
+        }
+    }
+
+    // When a waiter is late enough to look for deadlocks.
+    pub(crate) fn deadlock_check_after(&self) -> Duration {
+        self.wait_thresholds.unwrap_or(Thresholds::WAIT).warn
+    }
+
+    pub(crate) fn strategy(&self) -> Arc<dyn WaitStrategy> {
+        Arc::clone(
+            &self
+                .strategy
+                .read()
+                .unwrap_or_else(StdPoisonError::into_inner),
+        )
+    }
+
+    pub(crate) fn set_strategy(&self, strategy: Arc<dyn WaitStrategy>) {
+        *self
+            .strategy
+            .write()
+            .unwrap_or_else(StdPoisonError::into_inner) = strategy;
+    }
+
+    pub(crate) fn released(&self) {
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::released(self.id);
+        self.holder.clear();
+    }
+}
+
+#[cfg(any(feature = "registry", feature = "lockdep"))]
+impl Drop for LockState {
+    fn drop(&mut self) {
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::dropped(self.id);
+        #[cfg(feature = "registry")]
+        crate::registry::leave(self);
+    }
+}
+
+/// Identifies a lock in reports that may outlive it.
+#[derive(Clone, Debug)]
+pub struct LockInfo {
+    pub(crate) id: usize,
+    pub(crate) kind: &'static str,
+    pub(crate) name: Option<Arc<str>>,
+    pub(crate) created: CallSite,
+}
+
+impl LockInfo {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// `"Mutex"` or `"RwLock"`.
+    pub fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Where the lock was created, which identifies its lock class.
+    pub fn created(&self) -> CallSite {
+        self.created
+    }
+
+    pub(crate) fn label(&self) -> Label<'_> {
+        Label {
+            kind: self.kind,
+            id: self.id,
+            name: self.name.as_deref(),
+            created: self.created,
+        }
+    }
+}
+
+impl fmt::Display for LockInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.label().fmt(f)
+    }
+}
+
+// How messages refer to a lock, as in "Mutex #3 'cache' (created at
+// src/cache.rs:42)".
+#[derive(Clone, Copy)]
+pub(crate) struct Label<'a> {
+    pub(crate) kind: &'static str,
+    pub(crate) id: usize,
+    pub(crate) name: Option<&'a str>,
+    pub(crate) created: CallSite,
+}
+
+impl fmt::Display for Label<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} #{}", self.kind, self.id)?;
+        if let Some(name) = self.name {
+            write!(f, " '{}'", name)?;
+        }
+        if self.created.file().is_some() {
+            write!(f, " (created at {})", self.created)?;
+        }
+        Ok(())
+    }
+}
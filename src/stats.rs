@@ -0,0 +1,159 @@
+use std::{
+    convert::TryFrom,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use crate::CallSite;
+
+/// A point-in-time copy of the counters kept by a lock.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LockStats {
+    /// Where the lock was created, which identifies its lock class.
+    pub created: CallSite,
+    pub acquisitions: u64,
+    /// Acquisitions that found the lock taken, including those that then
+    /// gave up. Their waits count towards `total_wait` and `max_wait`.
+    pub contended: u64,
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+    pub total_hold: Duration,
+    pub max_hold: Duration,
+    pub poisonings: u64,
+    pub try_failures: u64,
+    /// Timed acquisitions that gave up at their deadline.
+    pub timeouts: u64,
+    /// Failed attempts made by waiters before getting the lock, summed over
+    /// all of them.
+    pub retries: u64,
+}
+
+impl LockStats {
+    /// Adds the counters of another lock, typically one of the same class.
+    pub fn merge(&mut self, other: &LockStats) {
+        self.acquisitions += other.acquisitions;
+        self.contended += other.contended;
+        self.total_wait += other.total_wait;
+        self.max_wait = self.max_wait.max(other.max_wait);
+        self.total_hold += other.total_hold;
+        self.max_hold = self.max_hold.max(other.max_hold);
+        self.poisonings += other.poisonings;
+        self.try_failures += other.try_failures;
+        self.timeouts += other.timeouts;
+        self.retries += other.retries;
+    }
+}
+
+// Counters are `usize` so they work on targets without 64-bit atomics. On
+// 32-bit targets the microsecond sums saturate after about 71 minutes.
+#[derive(Debug, Default)]
+pub(crate) struct Stats {
+    acquisitions: AtomicUsize,
+    contended: AtomicUsize,
+    total_wait_us: AtomicUsize,
+    max_wait_us: AtomicUsize,
+    total_hold_us: AtomicUsize,
+    max_hold_us: AtomicUsize,
+    poisonings: AtomicUsize,
+    try_failures: AtomicUsize,
+    timeouts: AtomicUsize,
+    retries: AtomicUsize,
+}
+
+impl Stats {
+    pub(crate) fn record_acquire(&self, waited: Duration, contended: bool) {
+        let us = micros_usize(waited);
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        if contended {
+            self.contended.fetch_add(1, Ordering::Relaxed);
+        }
+        saturating_add(&self.total_wait_us, us);
+        self.max_wait_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    // A contended acquisition that ended without the lock.
+    pub(crate) fn record_failed_wait(&self, waited: Duration, retries: u32) {
+        let us = micros_usize(waited);
+        self.contended.fetch_add(1, Ordering::Relaxed);
+        saturating_add(&self.total_wait_us, us);
+        self.max_wait_us.fetch_max(us, Ordering::Relaxed);
+        self.record_retries(retries);
+    }
+
+    pub(crate) fn record_timeout(&self) {
+        self.timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_retries(&self, retries: u32) {
+        saturating_add(
+            &self.retries,
+            usize::try_from(retries).unwrap_or(usize::MAX),
+        );
+    }
+
+    pub(crate) fn record_release(&self, held: Duration) {
+        let us = micros_usize(held);
+        saturating_add(&self.total_hold_us, us);
+        self.max_hold_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_poison(&self) {
+        self.poisonings.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_try_failure(&self) {
+        self.try_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> LockStats {
+        LockStats {
+            created: CallSite::default(),
+            acquisitions: load(&self.acquisitions),
+            contended: load(&self.contended),
+            total_wait: Duration::from_micros(load(&self.total_wait_us)),
+            max_wait: Duration::from_micros(load(&self.max_wait_us)),
+            total_hold: Duration::from_micros(load(&self.total_hold_us)),
+            max_hold: Duration::from_micros(load(&self.max_hold_us)),
+            poisonings: load(&self.poisonings),
+            try_failures: load(&self.try_failures),
+            timeouts: load(&self.timeouts),
+            retries: load(&self.retries),
+        }
+    }
+
+    pub(crate) fn reset(&self) {
+        for counter in &[
+            &self.acquisitions,
+            &self.contended,
+            &self.total_wait_us,
+            &self.max_wait_us,
+            &self.total_hold_us,
+            &self.max_hold_us,
+            &self.poisonings,
+            &self.try_failures,
+            &self.timeouts,
+            &self.retries,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(any(feature = "binlog", feature = "chrome-trace", feature = "prometheus"))]
+pub(crate) fn micros(d: Duration) -> u64 {
+    u64::try_from(d.as_micros()).unwrap_or(u64::MAX)
+}
+
+pub(crate) fn micros_usize(d: Duration) -> usize {
+    usize::try_from(d.as_micros()).unwrap_or(usize::MAX)
+}
+
+fn saturating_add(counter: &AtomicUsize, n: usize) {
+    let _ = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
+        Some(v.saturating_add(n))
+    });
+}
+
+fn load(counter: &AtomicUsize) -> u64 {
+    u64::try_from(counter.load(Ordering::Relaxed)).unwrap_or(u64::MAX)
+}
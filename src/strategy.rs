@@ -0,0 +1,114 @@
+use std::{
+    collections::hash_map::RandomState,
+    convert::TryFrom,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+use crate::DEFAULT_SPIN;
+
+/// What a waiter does before trying a contended lock again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pause {
+    Sleep(Duration),
+    /// `std::thread::yield_now`.
+    Yield,
+    /// A single `std::hint::spin_loop`.
+    Spin,
+    /// Give up retrying and block in the underlying lock until it is free.
+    /// Timed acquisitions cannot block past their deadline, so they poll
+    /// with short sleeps instead.
+    Block,
+}
+
+/// Decides how a thread waits for a contended lock.
+pub trait WaitStrategy: fmt::Debug + Send + Sync {
+    /// Called after every failed attempt; `attempt` counts them from zero
+    /// for this waiter alone.
+    fn pause(&self, attempt: u32, waited: Duration) -> Pause;
+}
+
+/// Sleeps twice as long after every miss, up to `max`. With `jitter`, each
+/// sleep is shortened by a random amount of up to half, so waiters woken
+/// together do not retry in lockstep.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExponentialBackoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub jitter: bool,
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_micros(DEFAULT_SPIN as u64),
+            max: Duration::from_millis(100),
+            jitter: true,
+        }
+    }
+}
+
+impl WaitStrategy for ExponentialBackoff {
+    fn pause(&self, attempt: u32, _waited: Duration) -> Pause {
+        let nap = 2u32
+            .checked_pow(attempt)
+            .and_then(|factor| self.initial.checked_mul(factor))
+            .map_or(self.max, |nap| nap.min(self.max));
+        if !self.jitter {
+            return Pause::Sleep(nap);
+        }
+        let half = u64::try_from(nap.as_nanos() / 2).unwrap_or(u64::MAX);
+        Pause::Sleep(nap - Duration::from_nanos(random() % half.saturating_add(1)))
+    }
+}
+
+/// Yields the thread between attempts and never sleeps. Only worth it for
+/// locks held very briefly.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct YieldSpin;
+
+impl WaitStrategy for YieldSpin {
+    fn pause(&self, _attempt: u32, _waited: Duration) -> Pause {
+        Pause::Yield
+    }
+}
+
+/// Busy-spins for `spins` attempts, then blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpinThenBlock {
+    pub spins: u32,
+}
+
+impl Default for SpinThenBlock {
+    fn default() -> Self {
+        Self { spins: 100 }
+    }
+}
+
+impl WaitStrategy for SpinThenBlock {
+    fn pause(&self, attempt: u32, _waited: Duration) -> Pause {
+        if attempt < self.spins {
+            Pause::Spin
+        } else {
+            Pause::Block
+        }
+    }
+}
+
+/// Blocks in the underlying lock straight away, like `std::sync::Mutex`.
+/// A background thread keeps reporting the wait to the observers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Blocking;
+
+impl WaitStrategy for Blocking {
+    fn pause(&self, _attempt: u32, _waited: Duration) -> Pause {
+        Pause::Block
+    }
+}
+
+// Good enough for jitter, without pulling in a dependency: every
+// `RandomState` is keyed differently.
+fn random() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
@@ -0,0 +1,45 @@
+use std::{
+    fmt,
+    thread::{self, Thread, ThreadId},
+};
+
+/// Identifies the thread that took part in a lock operation.
+#[derive(Clone, Debug)]
+pub struct ThreadInfo {
+    // The handle rather than a copy of the name, since one of these is
+    // taken on every acquisition.
+    thread: Thread,
+}
+
+impl ThreadInfo {
+    pub(crate) fn current() -> Self {
+        Self {
+            thread: thread::current(),
+        }
+    }
+
+    pub fn id(&self) -> ThreadId {
+        self.thread.id()
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.thread.name()
+    }
+}
+
+impl PartialEq for ThreadInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl Eq for ThreadInfo {}
+
+impl fmt::Display for ThreadInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "'{}'", name),
+            None => write!(f, "{:?}", self.id()),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use log::Level;
+
+/// How long a wait or hold may last before it is logged at each level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Thresholds {
+    pub debug: Duration,
+    pub info: Duration,
+    pub warn: Duration,
+    pub error: Duration,
+}
+
+impl Thresholds {
+    /// The defaults for waiting on a lock.
+    pub const WAIT: Self = Self {
+        debug: Duration::from_millis(50),
+        info: Duration::from_millis(500),
+        warn: Duration::from_secs(3),
+        error: Duration::from_secs(60),
+    };
+
+    /// The defaults for holding a lock.
+    pub const HOLD: Self = Self {
+        debug: Duration::from_millis(10),
+        info: Duration::from_millis(100),
+        warn: Duration::from_secs(1),
+        error: Duration::from_secs(10),
+    };
+
+    // The first threshold above `elapsed`, or `Duration::MAX` past `error`.
+    pub(crate) fn next_after(&self, elapsed: Duration) -> Duration {
+        [self.debug, self.info, self.warn, self.error]
+            .iter()
+            .copied()
+            .filter(|&threshold| threshold > elapsed)
+            .min()
+            .unwrap_or(Duration::MAX)
+    }
+
+    /// The level `elapsed` has reached, or `None` while it is below `debug`.
+    pub fn level(&self, elapsed: Duration) -> Option<Level> {
+        if elapsed >= self.error {
+            Some(Level::Error)
+        } else if elapsed >= self.warn {
+            Some(Level::Warn)
+        } else if elapsed >= self.info {
+            Some(Level::Info)
+        } else if elapsed >= self.debug {
+            Some(Level::Debug)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    sync::{Arc, Condvar as StdCondvar, Mutex as StdMutex, PoisonError as StdPoisonError},
+    thread::{self, JoinHandle, ThreadId},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    observer,
+    registry::{self, LockSnapshot},
+    Holder, Waiter,
+};
+
+type Hook = Box<dyn Fn(&Stall) + Send + Sync>;
+
+/// How often the watchdog scans, what it considers stuck, and who to tell.
+pub struct WatchdogConfig {
+    interval: Duration,
+    hold_threshold: Duration,
+    wait_threshold: Duration,
+    on_stall: Option<Hook>,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            hold_threshold: Duration::from_secs(10),
+            wait_threshold: Duration::from_secs(10),
+            on_stall: None,
+        }
+    }
+}
+
+impl WatchdogConfig {
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn hold_threshold(mut self, threshold: Duration) -> Self {
+        self.hold_threshold = threshold;
+        self
+    }
+
+    pub fn wait_threshold(mut self, threshold: Duration) -> Self {
+        self.wait_threshold = threshold;
+        self
+    }
+
+    /// Called on the watchdog thread for every new stall, after the observers.
+    pub fn on_stall(mut self, hook: impl Fn(&Stall) + Send + Sync + 'static) -> Self {
+        self.on_stall = Some(Box::new(hook));
+        self
+    }
+}
+
+impl fmt::Debug for WatchdogConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WatchdogConfig")
+            .field("interval", &self.interval)
+            .field("hold_threshold", &self.hold_threshold)
+            .field("wait_threshold", &self.wait_threshold)
+            .field("on_stall", &self.on_stall.is_some())
+            .finish()
+    }
+}
+
+/// A lock held, or waited on, for longer than the watchdog allows.
+#[derive(Clone, Debug)]
+pub enum Stall {
+    Hold { lock: LockSnapshot, holder: Holder },
+    Wait { lock: LockSnapshot, waiter: Waiter },
+}
+
+impl Stall {
+    pub fn lock(&self) -> &LockSnapshot {
+        match self {
+            Stall::Hold { lock, .. } | Stall::Wait { lock, .. } => lock,
+        }
+    }
+
+    // Identifies one hold or wait, so it is reported once however long it
+    // lasts.
+    fn key(&self) -> (usize, ThreadId, Instant, bool) {
+        match self {
+            Stall::Hold { lock, holder } => (lock.id, holder.thread.id(), holder.since, true),
+            Stall::Wait { lock, waiter } => (lock.id, waiter.thread.id(), waiter.since, false),
+        }
+    }
+}
+
+impl fmt::Display for Stall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.lock().label())?;
+        match self {
+            Stall::Hold { holder, .. } => write!(f, " stuck: {}", holder),
+            Stall::Wait { lock, waiter } => {
+                write!(f, " stuck: {}", waiter)?;
+                match &lock.holder {
+                    Some(holder) => write!(f, ", {}", holder),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// A background thread that periodically reports stuck locks. Stops when
+/// dropped.
+#[derive(Debug)]
+pub struct Watchdog {
+    stop: Arc<(StdMutex<bool>, StdCondvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    pub fn spawn(config: WatchdogConfig) -> Self {
+        let stop = Arc::new((StdMutex::new(false), StdCondvar::new()));
+        let thread = {
+            let stop = Arc::clone(&stop);
+            thread::Builder::new()
+                .name("trace-mutex-watchdog".into())
+                .spawn(move || run(&config, &stop))
+                .expect("failed to spawn the watchdog thread")
+        };
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        let (stopped, wake) = &*self.stop;
+        *stopped.lock().unwrap_or_else(StdPoisonError::into_inner) = true;
+        wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn run(config: &WatchdogConfig, stop: &(StdMutex<bool>, StdCondvar)) {
+    let (stopped, wake) = stop;
+    let mut reported = HashSet::new();
+    loop {
+        {
+            let guard = stopped.lock().unwrap_or_else(StdPoisonError::into_inner);
+            let (guard, _) = wake
+                .wait_timeout_while(guard, config.interval, |stopped| !*stopped)
+                .unwrap_or_else(StdPoisonError::into_inner);
+            if *guard {
+                return;
+            }
+        }
+
+        let stalls = scan(config);
+        // Forget holds and waits that have ended, so only new ones report.
+        let current: HashSet<_> = stalls.iter().map(Stall::key).collect();
+        reported.retain(|key| current.contains(key));
+        for stall in stalls {
+            if reported.insert(stall.key()) {
+                observer::notify(|o| o.on_stall(&stall));
+                if let Some(hook) = &config.on_stall {
+                    hook(&stall);
+                }
+            }
+        }
+    }
+}
+
+fn scan(config: &WatchdogConfig) -> Vec<Stall> {
+    let mut stalls = Vec::new();
+    for lock in registry::snapshot() {
+        if let Some(holder) = &lock.holder {
+            if holder.since.elapsed() >= config.hold_threshold {
+                stalls.push(Stall::Hold {
+                    lock: lock.clone(),
+                    holder: holder.clone(),
+                });
+            }
+        }
+        for waiter in &lock.waiters {
+            if waiter.since.elapsed() >= config.wait_threshold {
+                stalls.push(Stall::Wait {
+                    lock: lock.clone(),
+                    waiter: waiter.clone(),
+                });
+            }
+        }
+    }
+    stalls
+}
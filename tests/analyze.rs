@@ -0,0 +1,58 @@
+#![cfg(feature = "binlog")]
+
+use std::{
+    env, fs,
+    process::Command,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use trace_mutex::{set_observers, BinaryLogObserver, Mutex};
+
+#[test]
+fn analyzer_reports_a_generated_log() {
+    let path = env::temp_dir().join(format!("trace-mutex-analyze-{}.log", std::process::id()));
+    let log = Arc::new(BinaryLogObserver::create(&path).unwrap());
+    set_observers(vec![log.clone()]);
+
+    let (first, second) = (Mutex::named("first", 0), Mutex::named("second", 0));
+    thread::Builder::new()
+        .name("log-writer".into())
+        .spawn(move || {
+            for _ in 0..100 {
+                let _first = first.lock().unwrap();
+                let _second = second.lock().unwrap();
+            }
+            let _second = second.lock().unwrap();
+            let _first = first.lock().unwrap();
+            let until = Instant::now() + Duration::from_millis(2);
+            while Instant::now() < until {}
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+
+    set_observers(Vec::new());
+    log.flush().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_trace-mutex-analyze"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    let _ = fs::remove_file(&path);
+    assert!(output.status.success());
+    let report = String::from_utf8(output.stdout).unwrap();
+
+    assert!(report.starts_with("404 events, 2 locks"), "{}", report);
+    assert!(
+        report.contains("on thread 'log-writer', but also"),
+        "{}",
+        report
+    );
+    assert!(
+        report.contains("Threads by total wait:\n  thread 'log-writer'"),
+        "{}",
+        report
+    );
+    assert!(report.contains("(101 holds)"), "{}", report);
+}
@@ -0,0 +1,91 @@
+#![cfg(feature = "binlog")]
+
+use std::{
+    io::{self, ErrorKind, Write},
+    sync::{Arc, Mutex as StdMutex},
+};
+
+use trace_mutex::{
+    binlog::{EventKind, Reader, Record, MAGIC},
+    set_observers, BinaryLogObserver, Mutex,
+};
+
+#[derive(Clone, Default)]
+struct Shared(Arc<StdMutex<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn events_read_back_after_their_definitions() {
+    let out = Shared::default();
+    let log = Arc::new(BinaryLogObserver::new(out.clone()).unwrap());
+    set_observers(vec![log.clone()]);
+
+    let lock = Mutex::named("binlog", 0);
+    *lock.lock().unwrap() += 1;
+    *lock.lock().unwrap() += 1;
+
+    set_observers(Vec::new());
+    log.flush().unwrap();
+    let bytes = out.0.lock().unwrap().clone();
+
+    let mut sites = Vec::new();
+    let mut locks = Vec::new();
+    let mut threads = Vec::new();
+    let mut kinds = Vec::new();
+    for record in Reader::new(&bytes[..]).unwrap() {
+        match record.unwrap() {
+            Record::Site { index, .. } => {
+                assert_eq!(index as usize, sites.len());
+                sites.push(index);
+            }
+            Record::Lock { id, name, created } => {
+                assert!(sites.contains(&created));
+                assert_eq!(name, "binlog");
+                locks.push(id);
+            }
+            Record::Thread { index, .. } => threads.push(index),
+            Record::Event(event) => {
+                assert!(sites.contains(&event.site));
+                assert!(locks.contains(&event.lock));
+                assert!(threads.contains(&event.thread));
+                kinds.push(event.kind);
+            }
+        }
+    }
+    assert_eq!(locks.len(), 1);
+    assert_eq!(
+        kinds,
+        [
+            EventKind::Acquired,
+            EventKind::Released,
+            EventKind::Acquired,
+            EventKind::Released
+        ]
+    );
+}
+
+#[test]
+fn oversized_string_length_is_not_allocated() {
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(1);
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+    bytes.extend_from_slice(b"short");
+
+    let err = Reader::new(&bytes[..])
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+}
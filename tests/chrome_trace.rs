@@ -0,0 +1,66 @@
+#![cfg(feature = "chrome-trace")]
+
+use std::{
+    io::{self, Write},
+    sync::{mpsc, Arc, Mutex as StdMutex},
+    thread,
+    time::Duration,
+};
+
+use serde_json::Value;
+use trace_mutex::{set_observers, ChromeTraceObserver, Mutex};
+
+#[derive(Clone, Default)]
+struct Shared(Arc<StdMutex<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn only_contended_acquisitions_get_a_wait_slice() {
+    let out = Shared::default();
+    let chrome = Arc::new(ChromeTraceObserver::new(out.clone()));
+    set_observers(vec![chrome.clone()]);
+
+    let mutex = Arc::new(Mutex::named("timeline", 0));
+    for _ in 0..3 {
+        *mutex.lock().unwrap() += 1;
+    }
+    let (locked, is_locked) = mpsc::channel();
+    let holder = {
+        let mutex = Arc::clone(&mutex);
+        thread::spawn(move || {
+            let _guard = mutex.lock().unwrap();
+            locked.send(()).unwrap();
+            thread::sleep(Duration::from_millis(20));
+        })
+    };
+    is_locked.recv().unwrap();
+    *mutex.lock().unwrap() += 1;
+    holder.join().unwrap();
+
+    set_observers(Vec::new());
+    chrome.finish().unwrap();
+    let json: Value = serde_json::from_slice(&out.0.lock().unwrap()).unwrap();
+    let slices: Vec<&Value> = json
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|slice| slice["args"]["name"] == "timeline")
+        .collect();
+    let count = |phase: &str| slices.iter().filter(|s| s["cat"] == phase).count();
+
+    assert_eq!(count("hold"), 5);
+    assert_eq!(count("wait"), 1);
+    let wait = slices.iter().find(|s| s["cat"] == "wait").unwrap();
+    assert!(wait["dur"].as_u64().unwrap() >= 10_000, "{}", wait);
+    assert_eq!(wait["ph"], "X");
+}
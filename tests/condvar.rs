@@ -0,0 +1,75 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use trace_mutex::{Condvar, Mutex, PoisonPolicy};
+
+#[test]
+fn hold_before_wait_is_recorded() {
+    let pair = Arc::new((Mutex::new(false), Condvar::new()));
+    let guard = pair.0.lock().unwrap();
+    thread::sleep(Duration::from_millis(50));
+
+    let notifier = {
+        let pair = Arc::clone(&pair);
+        thread::spawn(move || {
+            *pair.0.lock().unwrap() = true;
+            pair.1.notify_one();
+        })
+    };
+    let guard = pair.1.wait_while(guard, |ready| !*ready).unwrap();
+    drop(guard);
+    notifier.join().unwrap();
+
+    let stats = pair.0.stats();
+    assert!(stats.max_hold >= Duration::from_millis(50));
+    assert!(stats.acquisitions >= 3);
+}
+
+#[test]
+fn wait_applies_poison_policy() {
+    let pair = Arc::new((
+        Mutex::builder().on_poison(PoisonPolicy::Ignore).build(()),
+        Condvar::new(),
+    ));
+    let guard = pair.0.lock().unwrap();
+    let poisoner = {
+        let pair = Arc::clone(&pair);
+        thread::spawn(move || {
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+                let _guard = pair.0.lock().unwrap();
+                pair.1.notify_one();
+                panic!("poison");
+            }));
+        })
+    };
+    let result = pair.1.wait_timeout(guard, Duration::from_secs(5));
+    poisoner.join().unwrap();
+    assert!(result.is_ok());
+    assert!(pair.0.is_poisoned());
+}
+
+#[test]
+fn reacquire_after_timeout_is_recorded() {
+    let pair = Arc::new((Mutex::new(()), Condvar::new()));
+    let guard = pair.0.lock().unwrap();
+    let holder = {
+        let pair = Arc::clone(&pair);
+        thread::spawn(move || {
+            let _guard = pair.0.lock().unwrap();
+            thread::sleep(Duration::from_millis(150));
+        })
+    };
+    let (guard, timeout) = pair
+        .1
+        .wait_timeout(guard, Duration::from_millis(30))
+        .unwrap();
+    assert!(timeout.timed_out());
+    drop(guard);
+    holder.join().unwrap();
+
+    assert!(pair.0.stats().max_wait >= Duration::from_millis(50));
+}
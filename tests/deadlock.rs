@@ -0,0 +1,98 @@
+use std::{
+    sync::{Arc, Barrier, Mutex as StdMutex},
+    thread,
+    time::Duration,
+};
+
+use trace_mutex::{add_observer, set_panic_on_deadlock, Deadlock, LockObserver, Mutex, Thresholds};
+
+#[derive(Default)]
+struct Reports(StdMutex<Vec<String>>);
+
+impl LockObserver for Reports {
+    fn on_deadlock(&self, deadlock: &Deadlock) {
+        self.0.lock().unwrap().push(deadlock.to_string());
+    }
+}
+
+impl Reports {
+    fn about(&self, name: &str) -> Vec<String> {
+        let reports = self.0.lock().unwrap();
+        reports
+            .iter()
+            .filter(|report| report.contains(name))
+            .cloned()
+            .collect()
+    }
+}
+
+// Checked for deadlocks from 5ms into a wait.
+fn quick(name: &str) -> Arc<Mutex<()>> {
+    let thresholds = Thresholds {
+        debug: Duration::from_millis(1),
+        info: Duration::from_millis(2),
+        warn: Duration::from_millis(5),
+        error: Duration::from_secs(60),
+    };
+    Arc::new(
+        Mutex::builder()
+            .name(name)
+            .wait_thresholds(thresholds)
+            .build(()),
+    )
+}
+
+// Locks `first` then `second` on one thread and `second` then `first` on
+// another, both at once, and returns whether each thread panicked.
+fn cross(
+    first: &Arc<Mutex<()>>,
+    second: &Arc<Mutex<()>>,
+    then: impl Fn(&Mutex<()>) + Send + Sync + 'static,
+) -> Vec<bool> {
+    let barrier = Arc::new(Barrier::new(2));
+    let then = Arc::new(then);
+    let threads: Vec<_> = [(first, second), (second, first)]
+        .iter()
+        .map(|&(held, wanted)| {
+            let (held, wanted) = (Arc::clone(held), Arc::clone(wanted));
+            let (barrier, then) = (Arc::clone(&barrier), Arc::clone(&then));
+            thread::spawn(move || {
+                let _held = held.lock().unwrap();
+                barrier.wait();
+                then(&wanted);
+            })
+        })
+        .collect();
+    threads
+        .into_iter()
+        .map(|thread| thread.join().is_err())
+        .collect()
+}
+
+#[test]
+fn deadlock_is_reported_once_and_panics_when_asked() {
+    let reports = Arc::new(Reports::default());
+    add_observer(reports.clone());
+
+    // Timed waits end the deadlock once it has been seen.
+    let (a, b) = (quick("reported-a"), quick("reported-b"));
+    let panicked = cross(&a, &b, |wanted| {
+        let _ = wanted.try_lock_for(Duration::from_millis(300));
+    });
+    assert_eq!(panicked, [false, false]);
+    let found = reports.about("'reported-a'");
+    assert_eq!(found.len(), 1, "{:?}", found);
+    assert!(found[0].starts_with("Deadlock detected:"));
+    assert!(found[0].contains("'reported-b'"));
+
+    // The thread that finds the cycle panics and releases its lock, which
+    // lets the other one through.
+    set_panic_on_deadlock(true);
+    let (a, b) = (quick("panicking-a"), quick("panicking-b"));
+    let panicked = cross(&a, &b, |wanted| {
+        let _ = wanted.lock();
+    });
+    set_panic_on_deadlock(false);
+    assert_eq!(panicked.iter().filter(|&&p| p).count(), 1);
+    assert_eq!(reports.about("'panicking-a'").len(), 1);
+}
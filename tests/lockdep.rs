@@ -0,0 +1,58 @@
+#![cfg(feature = "lockdep")]
+
+use std::sync::Mutex as StdMutex;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use trace_mutex::Mutex;
+
+static WARNINGS: StdMutex<Vec<String>> = StdMutex::new(Vec::new());
+
+struct Capture;
+
+impl Log for Capture {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.enabled(record.metadata()) {
+            WARNINGS.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn class_a() -> Mutex<()> {
+    Mutex::new(())
+}
+
+fn class_b() -> Mutex<()> {
+    Mutex::new(())
+}
+
+#[test]
+fn inversion_across_instances_of_the_same_classes() {
+    log::set_logger(&Capture).unwrap();
+    log::set_max_level(LevelFilter::Warn);
+
+    let (a0, b1) = (class_a(), class_b());
+    {
+        let _a = a0.lock().unwrap();
+        let _b = b1.lock().unwrap();
+    }
+    drop((a0, b1));
+
+    let (a2, b3) = (class_a(), class_b());
+    {
+        let _b = b3.lock().unwrap();
+        let _a = a2.lock().unwrap();
+    }
+
+    let warnings = WARNINGS.lock().unwrap();
+    assert!(
+        warnings.iter().any(|w| w.starts_with("Potential deadlock")),
+        "no inversion reported: {:?}",
+        *warnings
+    );
+}
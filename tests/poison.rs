@@ -0,0 +1,47 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use trace_mutex::{Mutex, RwLock};
+
+struct LockOnDrop<'a>(&'a Mutex<u32>);
+
+impl Drop for LockOnDrop<'_> {
+    fn drop(&mut self) {
+        *self.0.lock().unwrap() += 1;
+    }
+}
+
+#[test]
+fn panic_while_held_poisons() {
+    let mutex = Mutex::new(0);
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        let _guard = mutex.lock().unwrap();
+        panic!("poison");
+    }));
+    assert!(mutex.is_poisoned());
+    assert!(mutex.poisoned_by().is_some());
+    assert_eq!(mutex.stats().poisonings, 1);
+}
+
+#[test]
+fn lock_taken_during_unwinding_does_not_poison() {
+    let mutex = Mutex::new(0);
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        let _on_drop = LockOnDrop(&mutex);
+        panic!("unwinding");
+    }));
+    assert!(!mutex.is_poisoned());
+    assert!(mutex.poisoned_by().is_none());
+    assert_eq!(mutex.stats().poisonings, 0);
+    assert_eq!(*mutex.lock().unwrap(), 1);
+}
+
+#[test]
+fn panic_while_writing_poisons_rwlock() {
+    let rwlock = RwLock::new(0);
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        let _guard = rwlock.write().unwrap();
+        panic!("poison");
+    }));
+    assert!(rwlock.is_poisoned());
+    assert_eq!(rwlock.stats().poisonings, 1);
+}
@@ -0,0 +1,28 @@
+#![cfg(feature = "prometheus")]
+
+use std::sync::Arc;
+
+use trace_mutex::{set_observers, Mutex, PrometheusObserver};
+
+fn per_request() -> Mutex<u32> {
+    Mutex::new(0)
+}
+
+#[test]
+fn short_lived_locks_share_their_class_series() {
+    let prometheus = Arc::new(PrometheusObserver::new());
+    set_observers(vec![prometheus.clone()]);
+
+    for _ in 0..100 {
+        let lock = per_request();
+        *lock.lock().unwrap() += 1;
+    }
+
+    let rendered = prometheus.render();
+    let acquisitions: Vec<&str> = rendered
+        .lines()
+        .filter(|line| line.starts_with("trace_mutex_acquisitions_total{"))
+        .collect();
+    assert_eq!(acquisitions.len(), 1, "{}", rendered);
+    assert!(acquisitions[0].ends_with(" 100"), "{}", rendered);
+}
@@ -0,0 +1,61 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex as StdMutex},
+    time::{Duration, Instant},
+};
+
+use trace_mutex::{add_observer, Holder, LockEvent, LockObserver, Mutex, TimedLockError};
+
+#[test]
+fn timed_lock_reports_reentry() {
+    let mutex = Mutex::new(0);
+    let _guard = mutex.lock().unwrap();
+    for result in [
+        mutex.try_lock_for(Duration::from_secs(1)),
+        mutex.try_lock_for(Duration::MAX),
+        mutex.try_lock_until(Instant::now() + Duration::from_secs(1)),
+    ] {
+        match result {
+            Err(TimedLockError::Reentrant(_)) => {}
+            other => panic!("expected a reentry error, got {:?}", other.map(|_| ())),
+        }
+    }
+}
+
+#[test]
+fn lock_panics_on_reentry() {
+    let mutex = Mutex::new(0);
+    let _guard = mutex.lock().unwrap();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let _again = mutex.lock();
+    }));
+    assert!(result.is_err());
+}
+
+#[derive(Default)]
+struct Reentries(StdMutex<Vec<String>>);
+
+impl LockObserver for Reentries {
+    fn on_reentry(&self, event: &LockEvent<'_>) {
+        let held_at = event.holder_info().map(Holder::site);
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("{:?} {:?}", event.name(), held_at));
+    }
+}
+
+#[test]
+fn reentry_reaches_observers() {
+    let reentries = Arc::new(Reentries::default());
+    add_observer(reentries.clone());
+
+    let mutex = Mutex::named("reentered", 0);
+    let guard = mutex.lock().unwrap();
+    let held_at = mutex.holder().map(|holder| holder.site());
+    assert!(mutex.try_lock_for(Duration::from_secs(1)).is_err());
+    drop(guard);
+
+    let expected = format!("{:?} {:?}", Some("reentered"), held_at);
+    assert!(reentries.0.lock().unwrap().contains(&expected));
+}
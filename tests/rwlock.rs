@@ -0,0 +1,102 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex as StdMutex},
+    thread,
+    time::Duration,
+};
+
+use trace_mutex::{add_observer, LockEvent, LockObserver, RwLock};
+
+// Idents of the released and poisoned events of locks named "access" and
+// "poisoned".
+#[derive(Default)]
+struct Events {
+    released: StdMutex<Vec<String>>,
+    poisoned: StdMutex<Vec<String>>,
+}
+
+impl LockObserver for Events {
+    fn on_released(&self, event: &LockEvent<'_>) {
+        if event.name() == Some("access") {
+            self.released.lock().unwrap().push(event.to_string());
+        }
+    }
+
+    fn on_poisoned(&self, event: &LockEvent<'_>) {
+        if event.name() == Some("poisoned") {
+            self.poisoned.lock().unwrap().push(event.to_string());
+        }
+    }
+}
+
+#[test]
+fn readers_share_while_a_writer_waits() {
+    let rwlock = Arc::new(RwLock::named("shared", 0));
+    let (reading, is_reading) = mpsc::channel();
+    let (done, is_done) = mpsc::channel::<()>();
+    let reader = {
+        let rwlock = Arc::clone(&rwlock);
+        thread::spawn(move || {
+            let _guard = rwlock.read().unwrap();
+            reading.send(()).unwrap();
+            let _ = is_done.recv();
+        })
+    };
+    is_reading.recv().unwrap();
+
+    let guard = rwlock.read().unwrap();
+    let writer = {
+        let rwlock = Arc::clone(&rwlock);
+        thread::spawn(move || *rwlock.write().unwrap() += 1)
+    };
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(*guard, 0);
+    drop(guard);
+    drop(done);
+    reader.join().unwrap();
+    writer.join().unwrap();
+
+    assert_eq!(*rwlock.read().unwrap(), 1);
+    let stats = rwlock.stats();
+    assert_eq!(stats.acquisitions, 4);
+    assert_eq!(stats.contended, 1);
+    assert!(stats.max_wait >= Duration::from_millis(15));
+}
+
+#[test]
+fn released_event_names_the_access() {
+    let events = Arc::new(Events::default());
+    add_observer(events.clone());
+
+    let rwlock = RwLock::named("access", 0);
+    drop(rwlock.read().unwrap());
+    drop(rwlock.write().unwrap());
+
+    let released = events.released.lock().unwrap();
+    assert_eq!(released.len(), 2);
+    assert!(released[0].ends_with("(read)"), "{}", released[0]);
+    assert!(released[1].ends_with("(write)"), "{}", released[1]);
+}
+
+#[test]
+fn panicking_writer_poisons_readers() {
+    let events = Arc::new(Events::default());
+    add_observer(events.clone());
+
+    let rwlock = RwLock::named("poisoned", 0);
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut guard = rwlock.write().unwrap();
+        *guard = 1;
+        panic!("poison");
+    }));
+
+    assert!(rwlock.is_poisoned());
+    let guard = rwlock.read().unwrap_err().into_inner();
+    assert_eq!(*guard, 1);
+    drop(guard);
+    assert_eq!(rwlock.stats().poisonings, 1);
+
+    let poisoned = events.poisoned.lock().unwrap();
+    assert_eq!(poisoned.len(), 1);
+    assert!(poisoned[0].ends_with("(write)"), "{}", poisoned[0]);
+}
@@ -0,0 +1,143 @@
+use std::{
+    sync::{mpsc, Arc, Mutex as StdMutex},
+    thread,
+    time::Duration,
+};
+
+use trace_mutex::{
+    add_observer, Blocking, ExponentialBackoff, LockEvent, LockObserver, Mutex, Pause,
+    SpinThenBlock, Thresholds, WaitStrategy,
+};
+
+const NONE: Duration = Duration::from_secs(0);
+
+fn backoff(jitter: bool) -> ExponentialBackoff {
+    ExponentialBackoff {
+        initial: Duration::from_micros(100),
+        max: Duration::from_millis(10),
+        jitter,
+    }
+}
+
+#[test]
+fn backoff_doubles_up_to_max() {
+    let backoff = backoff(false);
+    assert_eq!(
+        backoff.pause(0, NONE),
+        Pause::Sleep(Duration::from_micros(100))
+    );
+    assert_eq!(
+        backoff.pause(1, NONE),
+        Pause::Sleep(Duration::from_micros(200))
+    );
+    assert_eq!(
+        backoff.pause(6, NONE),
+        Pause::Sleep(Duration::from_micros(6400))
+    );
+    assert_eq!(
+        backoff.pause(7, NONE),
+        Pause::Sleep(Duration::from_millis(10))
+    );
+    assert_eq!(
+        backoff.pause(40, NONE),
+        Pause::Sleep(Duration::from_millis(10))
+    );
+    assert_eq!(
+        backoff.pause(u32::MAX, NONE),
+        Pause::Sleep(Duration::from_millis(10))
+    );
+}
+
+#[test]
+fn jitter_shortens_by_at_most_half() {
+    let (plain, jittered) = (backoff(false), backoff(true));
+    for attempt in (0..12).chain(Some(u32::MAX)) {
+        let nap = match plain.pause(attempt, NONE) {
+            Pause::Sleep(nap) => nap,
+            other => panic!("expected a sleep, got {:?}", other),
+        };
+        for _ in 0..100 {
+            match jittered.pause(attempt, NONE) {
+                Pause::Sleep(d) => assert!(d <= nap && d >= nap / 2, "{:?} of {:?}", d, nap),
+                other => panic!("expected a sleep, got {:?}", other),
+            }
+        }
+    }
+}
+
+#[test]
+fn spin_then_block_blocks_after_its_spins() {
+    let strategy = SpinThenBlock { spins: 3 };
+    let pauses: Vec<Pause> = (0..5)
+        .map(|attempt| strategy.pause(attempt, NONE))
+        .collect();
+    assert_eq!(
+        pauses,
+        [
+            Pause::Spin,
+            Pause::Spin,
+            Pause::Spin,
+            Pause::Block,
+            Pause::Block
+        ]
+    );
+}
+
+// Still-waiting reports for the "blocked" lock, with the thread each was
+// made on.
+#[derive(Default)]
+struct StillWaiting(StdMutex<Vec<(Option<String>, Option<String>)>>);
+
+impl LockObserver for StillWaiting {
+    fn on_still_waiting(&self, event: &LockEvent<'_>) {
+        if event.name() == Some("blocked") {
+            self.0.lock().unwrap().push((
+                event.thread().name().map(str::to_owned),
+                thread::current().name().map(str::to_owned),
+            ));
+        }
+    }
+}
+
+#[test]
+fn blocked_waiter_is_reported_from_the_progress_thread() {
+    let reports = Arc::new(StillWaiting::default());
+    add_observer(reports.clone());
+
+    let mutex = Arc::new(
+        Mutex::builder()
+            .name("blocked")
+            .wait_strategy(Blocking)
+            .wait_thresholds(Thresholds {
+                debug: Duration::from_millis(5),
+                info: Duration::from_millis(10),
+                warn: Duration::from_millis(20),
+                error: Duration::from_secs(60),
+            })
+            .build(()),
+    );
+    let (locked, is_locked) = mpsc::channel();
+    let holder = {
+        let mutex = Arc::clone(&mutex);
+        thread::spawn(move || {
+            let _guard = mutex.lock().unwrap();
+            locked.send(()).unwrap();
+            thread::sleep(Duration::from_millis(100));
+        })
+    };
+    is_locked.recv().unwrap();
+    thread::Builder::new()
+        .name("waiter".into())
+        .spawn(move || drop(mutex.lock().unwrap()))
+        .unwrap()
+        .join()
+        .unwrap();
+    holder.join().unwrap();
+
+    let reports = reports.0.lock().unwrap();
+    assert!(!reports.is_empty());
+    for (waiter, reporter) in reports.iter() {
+        assert_eq!(waiter.as_deref(), Some("waiter"));
+        assert_eq!(reporter.as_deref(), Some("trace-mutex-progress"));
+    }
+}
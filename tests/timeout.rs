@@ -0,0 +1,93 @@
+use std::{
+    sync::{mpsc, Arc, Mutex as StdMutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use trace_mutex::{add_observer, LockEvent, LockObserver, Mutex, TimedLockError};
+
+// Runs `f` while another thread holds `mutex`.
+fn while_held<T: Send>(mutex: &Mutex<T>, f: impl FnOnce()) {
+    let (locked, is_locked) = mpsc::channel();
+    let (release, released) = mpsc::channel::<()>();
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            let _guard = mutex.lock().unwrap();
+            locked.send(()).unwrap();
+            let _ = released.recv();
+        });
+        is_locked.recv().unwrap();
+        f();
+        release.send(()).unwrap();
+    });
+}
+
+#[test]
+fn timeout_is_counted_with_its_wait() {
+    let mutex = Mutex::new(0);
+    let timeout = Duration::from_millis(50);
+    while_held(&mutex, || {
+        assert!(matches!(
+            mutex.try_lock_for(timeout),
+            Err(TimedLockError::Timeout(_))
+        ));
+    });
+
+    let stats = mutex.stats();
+    assert_eq!(stats.timeouts, 1);
+    assert_eq!(stats.contended, 1);
+    assert!(stats.total_wait >= timeout, "{:?}", stats);
+    assert!(stats.retries > 0);
+}
+
+#[test]
+fn reentry_is_counted_as_contended() {
+    let mutex = Mutex::new(0);
+    let _guard = mutex.lock().unwrap();
+    let deadline = Instant::now() + Duration::from_secs(1);
+    assert!(mutex.try_lock_until(deadline).is_err());
+    assert_eq!(mutex.stats().contended, 1);
+    assert_eq!(mutex.stats().timeouts, 0);
+}
+
+// Ids of the locks named "deadline" as they are acquired.
+#[derive(Default)]
+struct Ids(StdMutex<Vec<usize>>);
+
+impl LockObserver for Ids {
+    fn on_acquired(&self, event: &LockEvent<'_>) {
+        if event.name() == Some("deadline") {
+            self.0.lock().unwrap().push(event.id());
+        }
+    }
+}
+
+#[test]
+fn timeout_names_the_mutex_and_caller() {
+    let ids = Arc::new(Ids::default());
+    add_observer(ids.clone());
+
+    let mutex = Mutex::named("deadline", 0);
+    let timeout = Duration::from_millis(50);
+    while_held(&mutex, || {
+        let start = Instant::now();
+        let (result, line) = (mutex.try_lock_for(timeout), line!());
+        let elapsed = start.elapsed();
+        let err = match result {
+            Err(TimedLockError::Timeout(err)) => err,
+            _ => panic!("expected a timeout"),
+        };
+
+        assert_eq!(err.id(), ids.0.lock().unwrap()[0]);
+        assert_eq!(err.name(), Some("deadline"));
+        assert_eq!(err.site().file(), Some(file!()));
+        assert_eq!(err.site().line(), Some(line));
+        assert!(elapsed >= timeout, "{:?}", elapsed);
+        assert!(
+            elapsed < timeout + Duration::from_millis(40),
+            "{:?}",
+            elapsed
+        );
+        assert!(err.waited() >= timeout && err.waited() <= elapsed);
+    });
+}
@@ -0,0 +1,38 @@
+#![cfg(feature = "watchdog")]
+
+use std::{
+    sync::{Arc, Mutex as StdMutex},
+    thread,
+    time::Duration,
+};
+
+use trace_mutex::{Mutex, Stall, Watchdog, WatchdogConfig};
+
+#[test]
+fn each_stuck_hold_is_reported_once() {
+    let stalls = Arc::new(StdMutex::new(Vec::new()));
+    let config = WatchdogConfig::default()
+        .interval(Duration::from_millis(5))
+        .hold_threshold(Duration::from_millis(20))
+        .on_stall({
+            let stalls = Arc::clone(&stalls);
+            move |stall: &Stall| {
+                if stall.lock().name.as_deref() == Some("stuck") {
+                    stalls.lock().unwrap().push(stall.to_string());
+                }
+            }
+        });
+    let watchdog = Watchdog::spawn(config);
+
+    let mutex = Mutex::named("stuck", 0);
+    for _ in 0..2 {
+        let _guard = mutex.lock().unwrap();
+        thread::sleep(Duration::from_millis(100));
+    }
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(stalls.lock().unwrap().len(), 2, "{:?}", stalls);
+
+    // The hook goes away with the thread, once `stop` has joined it.
+    watchdog.stop();
+    assert_eq!(Arc::strong_count(&stalls), 1);
+}